@@ -1,16 +1,74 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Wire(String);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    W8,
+    W16,
+    W32,
+    W64,
+}
+
+impl Width {
+    pub fn bits(self) -> u64 {
+        match self {
+            Width::W8 => 8,
+            Width::W16 => 16,
+            Width::W32 => 32,
+            Width::W64 => 64,
+        }
+    }
+
+    pub fn mask(self) -> u64 {
+        u64::MAX >> (64 - self.bits())
+    }
+
+    fn shl(self, a: u64, b: u64) -> u64 {
+        if b >= self.bits() {
+            0
+        } else {
+            (a << b) & self.mask()
+        }
+    }
+
+    fn shr(self, a: u64, b: u64) -> u64 {
+        if b >= self.bits() {
+            0
+        } else {
+            a >> b
+        }
+    }
+}
+
+impl TryFrom<u64> for Width {
+    type Error = ();
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            8 => Ok(Width::W8),
+            16 => Ok(Width::W16),
+            32 => Ok(Width::W32),
+            64 => Ok(Width::W64),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Source {
-    Signal(u16),
+    Signal(u64),
     Wire(Wire),
     And(Box<Source>, Box<Source>),
     Or(Box<Source>, Box<Source>),
-    LShift(Box<Source>, i32),
-    RShift(Box<Source>, i32),
+    Xor(Box<Source>, Box<Source>),
+    Nand(Box<Source>, Box<Source>),
+    Add(Box<Source>, Box<Source>),
+    Sub(Box<Source>, Box<Source>),
+    LShift(Box<Source>, Box<Source>),
+    RShift(Box<Source>, Box<Source>),
     Not(Box<Source>),
 }
 
@@ -28,32 +86,30 @@ impl TryFrom<&str> for Source {
 
         let source: Source = match (t1, t2, t3) {
             (Some(x), None, None) => {
-                if let Ok(signal) = x.parse::<u16>() {
+                if let Ok(signal) = x.parse::<u64>() {
                     Source::Signal(signal)
                 } else {
                     Source::Wire(Wire(x.to_string()))
                 }
             }
             (Some("NOT"), Some(src), None) => Source::Not(Box::new(src.try_into().unwrap())),
-            (Some(a), Some(op), Some(b)) => match op {
-                "AND" => Source::And(
-                    Box::new(a.try_into().unwrap()),
-                    Box::new(b.try_into().unwrap()),
-                ),
-                "OR" => Source::Or(
+            (Some(a), Some(op), Some(b)) => {
+                let gate = match op {
+                    "AND" => Source::And,
+                    "OR" => Source::Or,
+                    "XOR" => Source::Xor,
+                    "NAND" => Source::Nand,
+                    "ADD" => Source::Add,
+                    "SUB" => Source::Sub,
+                    "LSHIFT" => Source::LShift,
+                    "RSHIFT" => Source::RShift,
+                    _ => Err(())?,
+                };
+                gate(
                     Box::new(a.try_into().unwrap()),
                     Box::new(b.try_into().unwrap()),
-                ),
-                "LSHIFT" => Source::LShift(
-                    Box::new(a.try_into().unwrap()),
-                    b.parse::<i32>().map_err(|_| ())?,
-                ),
-                "RSHIFT" => Source::RShift(
-                    Box::new(a.try_into().unwrap()),
-                    b.parse::<i32>().map_err(|_| ())?,
-                ),
-                _ => Err(())?,
-            },
+                )
+            }
             _ => Err(())?,
         };
 
@@ -61,6 +117,49 @@ impl TryFrom<&str> for Source {
     }
 }
 
+impl fmt::Display for Wire {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (a, op, b) = match self {
+            Source::Signal(signal) => return write!(f, "{}", signal),
+            Source::Wire(wire) => return write!(f, "{}", wire),
+            Source::Not(a) => return write!(f, "NOT {}", a),
+            Source::And(a, b) => (a, "AND", b),
+            Source::Or(a, b) => (a, "OR", b),
+            Source::Xor(a, b) => (a, "XOR", b),
+            Source::Nand(a, b) => (a, "NAND", b),
+            Source::Add(a, b) => (a, "ADD", b),
+            Source::Sub(a, b) => (a, "SUB", b),
+            Source::LShift(a, b) => (a, "LSHIFT", b),
+            Source::RShift(a, b) => (a, "RSHIFT", b),
+        };
+        write!(f, "{} {} {}", a, op, b)
+    }
+}
+
+impl Source {
+    fn wires(&self) -> Vec<&Wire> {
+        match self {
+            Source::Signal(_) => vec![],
+            Source::Wire(wire) => vec![wire],
+            Source::And(a, b)
+            | Source::Or(a, b)
+            | Source::Xor(a, b)
+            | Source::Nand(a, b)
+            | Source::Add(a, b)
+            | Source::Sub(a, b)
+            | Source::LShift(a, b)
+            | Source::RShift(a, b) => a.wires().into_iter().chain(b.wires()).collect(),
+            Source::Not(a) => a.wires(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Instruction {
     source: Source,
@@ -84,41 +183,225 @@ impl TryFrom<&str> for Instruction {
     }
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.source, self.wire)
+    }
+}
+
 fn wire_to_signal(
     source: Source,
     instructions: &mut HashMap<Wire, Source>,
-    cache: &mut HashMap<Wire, u16>,
-) -> u16 {
+    cache: &mut HashMap<Wire, u64>,
+    width: Width,
+) -> u64 {
+    let mut signal = |source: Box<Source>| wire_to_signal(*source, instructions, cache, width);
     match source {
-        Source::Signal(signal) => signal,
+        Source::Signal(signal) => signal & width.mask(),
         Source::Wire(wire) => {
             if let Some(signal) = cache.get(&wire) {
                 *signal
             } else {
-                let signal = wire_to_signal(instructions[&wire].clone(), instructions, cache);
+                let signal =
+                    wire_to_signal(instructions[&wire].clone(), instructions, cache, width);
                 cache.insert(wire, signal);
                 signal
             }
         }
-        Source::And(a, b) => {
-            wire_to_signal(*a, instructions, cache) & wire_to_signal(*b, instructions, cache)
-        }
-        Source::Or(a, b) => {
-            wire_to_signal(*a, instructions, cache) | wire_to_signal(*b, instructions, cache)
-        }
-        Source::LShift(a, b) => wire_to_signal(*a, instructions, cache) << b,
-        Source::RShift(a, b) => wire_to_signal(*a, instructions, cache) >> b,
-        Source::Not(a) => !wire_to_signal(*a, instructions, cache),
+        Source::And(a, b) => signal(a) & signal(b),
+        Source::Or(a, b) => signal(a) | signal(b),
+        Source::Xor(a, b) => signal(a) ^ signal(b),
+        Source::Nand(a, b) => !(signal(a) & signal(b)) & width.mask(),
+        Source::Add(a, b) => signal(a).wrapping_add(signal(b)) & width.mask(),
+        Source::Sub(a, b) => signal(a).wrapping_sub(signal(b)) & width.mask(),
+        Source::LShift(a, b) => width.shl(signal(a), signal(b)),
+        Source::RShift(a, b) => width.shr(signal(a), signal(b)),
+        Source::Not(a) => !signal(a) & width.mask(),
     }
 }
 
-fn run_instructions_for(wire: Wire, instructions: impl IntoIterator<Item = Instruction>) -> u16 {
+fn run_instructions_for(wire: Wire, instructions: impl IntoIterator<Item = Instruction>) -> u64 {
+    run_instructions_with_width(wire, instructions, Width::W16)
+}
+
+fn run_instructions_with_width(
+    wire: Wire,
+    instructions: impl IntoIterator<Item = Instruction>,
+    width: Width,
+) -> u64 {
     let mut instructions = instructions
         .into_iter()
         .map(|i| (i.wire, i.source))
         .collect::<HashMap<Wire, Source>>();
 
-    wire_to_signal(Source::Wire(wire), &mut instructions, &mut HashMap::new())
+    wire_to_signal(
+        Source::Wire(wire),
+        &mut instructions,
+        &mut HashMap::new(),
+        width,
+    )
+}
+
+// Follows wires that are plain copies of another wire or signal. Stops at the
+// first wire seen twice so that malformed cyclic circuits still terminate.
+fn resolve(source: &Source, definitions: &HashMap<Wire, Source>) -> Source {
+    let mut current = source.clone();
+    let mut visited = HashSet::new();
+    while let Source::Wire(wire) = &current {
+        visited.insert(wire.clone());
+        match definitions.get(wire) {
+            Some(Source::Wire(next)) if visited.contains(next) => break,
+            Some(next @ (Source::Signal(_) | Source::Wire(_))) => current = next.clone(),
+            _ => break,
+        }
+    }
+    current
+}
+
+fn simplify_source(source: &Source, definitions: &HashMap<Wire, Source>, width: Width) -> Source {
+    let mask = width.mask();
+    let resolve = |source: &Source| match resolve(source, definitions) {
+        Source::Signal(signal) => Source::Signal(signal & mask),
+        source => source,
+    };
+    match source {
+        Source::Signal(_) | Source::Wire(_) => resolve(source),
+        Source::And(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(a & b),
+            (Source::Signal(0), _) | (_, Source::Signal(0)) => Source::Signal(0),
+            (Source::Signal(m), x) | (x, Source::Signal(m)) if m == mask => x,
+            (a, b) if a == b => a,
+            (a, b) => Source::And(Box::new(a), Box::new(b)),
+        },
+        Source::Or(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(a | b),
+            (Source::Signal(m), _) | (_, Source::Signal(m)) if m == mask => Source::Signal(mask),
+            (Source::Signal(0), x) | (x, Source::Signal(0)) => x,
+            (a, b) if a == b => a,
+            (a, b) => Source::Or(Box::new(a), Box::new(b)),
+        },
+        Source::Xor(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(a ^ b),
+            (Source::Signal(0), x) | (x, Source::Signal(0)) => x,
+            (a, b) if a == b => Source::Signal(0),
+            (a, b) => Source::Xor(Box::new(a), Box::new(b)),
+        },
+        Source::Nand(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(!(a & b) & mask),
+            (Source::Signal(0), _) | (_, Source::Signal(0)) => Source::Signal(mask),
+            (a, b) => Source::Nand(Box::new(a), Box::new(b)),
+        },
+        Source::Add(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(a.wrapping_add(b) & mask),
+            (Source::Signal(0), x) | (x, Source::Signal(0)) => x,
+            (a, b) => Source::Add(Box::new(a), Box::new(b)),
+        },
+        Source::Sub(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(a.wrapping_sub(b) & mask),
+            (x, Source::Signal(0)) => x,
+            (a, b) if a == b => Source::Signal(0),
+            (a, b) => Source::Sub(Box::new(a), Box::new(b)),
+        },
+        Source::LShift(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(width.shl(a, b)),
+            (Source::Signal(0), _) => Source::Signal(0),
+            (_, Source::Signal(b)) if b >= width.bits() => Source::Signal(0),
+            (x, Source::Signal(0)) => x,
+            (a, b) => Source::LShift(Box::new(a), Box::new(b)),
+        },
+        Source::RShift(a, b) => match (resolve(a), resolve(b)) {
+            (Source::Signal(a), Source::Signal(b)) => Source::Signal(width.shr(a, b)),
+            (Source::Signal(0), _) => Source::Signal(0),
+            (_, Source::Signal(b)) if b >= width.bits() => Source::Signal(0),
+            (x, Source::Signal(0)) => x,
+            (a, b) => Source::RShift(Box::new(a), Box::new(b)),
+        },
+        Source::Not(a) => match resolve(a) {
+            Source::Signal(a) => Source::Signal(!a & mask),
+            Source::Wire(wire) => match definitions.get(&wire) {
+                Some(Source::Not(inner)) => resolve(inner),
+                _ => Source::Not(Box::new(Source::Wire(wire))),
+            },
+            a => Source::Not(Box::new(a)),
+        },
+    }
+}
+
+// The text format has a single gate per line, so a wire used only once is only
+// inlined where it is copied as is (`t -> f`). A wire used once as a gate
+// operand has to keep its own line.
+fn inline_single_use_wires(instructions: &mut [Instruction], outputs: &[Wire]) {
+    let mut uses: HashMap<Wire, usize> = HashMap::new();
+    for wire in instructions.iter().flat_map(|i| i.source.wires()) {
+        *uses.entry(wire.clone()).or_default() += 1;
+    }
+
+    for i in 0..instructions.len() {
+        let Source::Wire(wire) = &instructions[i].source else {
+            continue;
+        };
+        if uses[wire] != 1 || outputs.contains(wire) || *wire == instructions[i].wire {
+            continue;
+        }
+        if let Some(definition) = instructions.iter().find(|d| d.wire == *wire) {
+            instructions[i].source = definition.source.clone();
+        }
+    }
+}
+
+fn remove_dead_wires(instructions: &mut Vec<Instruction>, outputs: &[Wire]) {
+    let definitions = instructions
+        .iter()
+        .map(|i| (&i.wire, &i.source))
+        .collect::<HashMap<_, _>>();
+
+    let mut live = HashSet::new();
+    let mut stack = outputs.iter().collect::<Vec<_>>();
+    while let Some(wire) = stack.pop() {
+        if live.insert(wire.clone()) {
+            if let Some(source) = definitions.get(wire) {
+                stack.extend(source.wires());
+            }
+        }
+    }
+
+    instructions.retain(|i| live.contains(&i.wire));
+}
+
+fn simplify(
+    instructions: impl IntoIterator<Item = Instruction>,
+    outputs: &[Wire],
+) -> Vec<Instruction> {
+    simplify_with_width(instructions, outputs, Width::W16)
+}
+
+fn simplify_with_width(
+    instructions: impl IntoIterator<Item = Instruction>,
+    outputs: &[Wire],
+    width: Width,
+) -> Vec<Instruction> {
+    let mut instructions = instructions.into_iter().collect::<Vec<_>>();
+    loop {
+        let definitions = instructions
+            .iter()
+            .map(|i| (i.wire.clone(), i.source.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let mut simplified = instructions
+            .iter()
+            .map(|i| Instruction {
+                source: simplify_source(&i.source, &definitions, width),
+                wire: i.wire.clone(),
+            })
+            .collect::<Vec<_>>();
+        inline_single_use_wires(&mut simplified, outputs);
+        remove_dead_wires(&mut simplified, outputs);
+
+        if simplified == instructions {
+            return simplified;
+        }
+        instructions = simplified;
+    }
 }
 
 fn main() {
@@ -136,7 +419,8 @@ fn main() {
         .unwrap()
         .source = Source::Signal(signal_in_a);
 
-    let result = run_instructions_for(Wire(String::from("a")), instructions);
+    let output = Wire(String::from("a"));
+    let result = run_instructions_for(output.clone(), simplify(instructions, &[output]));
     println!("{}", result);
 }
 
@@ -185,4 +469,137 @@ mod tests {
             456
         );
     }
+
+    #[test]
+    fn test_simplify_folds_constants() {
+        let input = include_str!("../sample.txt");
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+        let outputs = ["d", "e", "h"].map(|w| Wire(String::from(w)));
+
+        let simplified = simplify(instructions, &outputs)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(simplified, ["72 -> d", "507 -> e", "65412 -> h"]);
+    }
+
+    #[test]
+    fn test_simplify_removes_identities_and_inlines() {
+        let input =
+            "a LSHIFT 0 -> b\nNOT b -> c\nNOT c -> d\nd AND e -> t\nt -> f\nf RSHIFT 0 -> g";
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+
+        let simplified = simplify(instructions, &[Wire(String::from("g"))])
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(simplified, ["a AND e -> g"]);
+    }
+
+    #[test]
+    fn test_simplify_keeps_single_use_gate_operands() {
+        let input = "x AND y -> t\nt OR z -> u";
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+
+        let simplified = simplify(instructions.clone(), &[Wire(String::from("u"))]);
+
+        assert_eq!(simplified, instructions);
+        for instruction in &simplified {
+            assert_eq!(
+                Instruction::try_from(instruction.to_string().as_str()).as_ref(),
+                Ok(instruction)
+            );
+        }
+    }
+
+    #[test]
+    fn test_simplify_preserves_signal() {
+        let input = include_str!("../input.txt");
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+        let output = Wire(String::from("a"));
+
+        let simplified = simplify(instructions.clone(), std::slice::from_ref(&output));
+
+        assert_eq!(
+            run_instructions_for(output.clone(), simplified),
+            run_instructions_for(output, instructions)
+        );
+    }
+
+    #[test]
+    fn test_extended_gates() {
+        let input = "200 -> a\n100 -> b\n3 -> s\na XOR b -> x\na NAND b -> n\na ADD b -> p\nb SUB a -> m\na LSHIFT s -> l\na RSHIFT s -> r";
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+        let run = |wire: &str, width: u64| {
+            run_instructions_with_width(
+                Wire(String::from(wire)),
+                instructions.clone(),
+                width.try_into().unwrap(),
+            )
+        };
+
+        assert_eq!(run("x", 8), 200 ^ 100);
+        assert_eq!(run("n", 8), !(200 & 100) & 0xff);
+        assert_eq!(run("p", 8), 44);
+        assert_eq!(run("p", 16), 300);
+        assert_eq!(run("m", 8), 156);
+        assert_eq!(run("m", 64), u64::MAX - 99);
+        assert_eq!(run("l", 8), 64);
+        assert_eq!(run("l", 16), 1600);
+        assert_eq!(run("r", 32), 25);
+    }
+
+    #[test]
+    fn test_shift_beyond_width() {
+        let input = "1 -> a\n8 -> s\n64 -> t\na LSHIFT s -> b\na LSHIFT t -> c\na RSHIFT t -> d";
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+        let run = |wire: &str, width: Width| {
+            run_instructions_with_width(Wire(String::from(wire)), instructions.clone(), width)
+        };
+
+        assert_eq!(run("b", Width::W8), 0);
+        assert_eq!(run("b", Width::W16), 256);
+        assert_eq!(run("c", Width::W64), 0);
+        assert_eq!(run("d", Width::W64), 0);
+    }
+
+    #[test]
+    fn test_simplify_with_width() {
+        let input = "x AND 255 -> a\na XOR a -> b\nx LSHIFT 8 -> c\nc ADD b -> d";
+        let instructions = input
+            .lines()
+            .flat_map(Instruction::try_from)
+            .collect::<Vec<_>>();
+        let outputs = ["a", "d"].map(|w| Wire(String::from(w)));
+
+        let simplify = |width: Width| {
+            simplify_with_width(instructions.clone(), &outputs, width)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(simplify(Width::W8), ["x -> a", "0 -> d"]);
+        assert_eq!(simplify(Width::W16), ["x AND 255 -> a", "x LSHIFT 8 -> d"]);
+    }
 }
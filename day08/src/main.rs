@@ -1,95 +1,339 @@
-fn count_chars_literals_values(input: &str) -> (usize, usize) {
-    let mut literals = 0;
-    let mut values = 0;
+use std::fmt::Write;
 
-    let mut escaping = false;
-    let mut escaping_hex_remaining = 0;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeError {
+    MissingQuotes,
+    UnescapedQuote(usize),
+    TruncatedEscape(usize),
+    InvalidEscape(usize),
+    InvalidHex(usize),
+    OutOfRange(usize),
+    InvalidUnicode(usize),
+}
 
-    for c in input.chars() {
-        match c {
-            '\\' => {
-                if escaping {
-                    // "\\"
-                    escaping = false;
-                    values += 1;
-                } else {
-                    // "\"
-                    escaping = true;
+// Decodes the escape sequence following a backslash. `rest` starts right after
+// the backslash and `position` is the backslash offset used for errors. Returns
+// how many bytes of `rest` the escape consumed.
+trait EscapeDialect {
+    fn decode_escape(
+        &self,
+        rest: &[u8],
+        position: usize,
+        decoded: &mut Vec<u8>,
+    ) -> Result<usize, EscapeError>;
+}
+
+struct Puzzle;
+struct Rust;
+struct Json;
+struct C;
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|d| d as u8)
+}
+
+fn hex_digits(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |acc, &d| {
+        acc.checked_mul(16)?.checked_add(hex_value(d)? as u32)
+    })
+}
+
+fn simple_escape(table: &[(u8, u8)], c: u8, decoded: &mut Vec<u8>) -> Option<usize> {
+    let (_, value) = table.iter().find(|(escape, _)| *escape == c)?;
+    decoded.push(*value);
+    Some(1)
+}
+
+fn push_char(decoded: &mut Vec<u8>, c: char) {
+    decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+impl EscapeDialect for Puzzle {
+    fn decode_escape(
+        &self,
+        rest: &[u8],
+        position: usize,
+        decoded: &mut Vec<u8>,
+    ) -> Result<usize, EscapeError> {
+        match rest.first() {
+            Some(b'x') => {
+                let hex = rest
+                    .get(1..3)
+                    .ok_or(EscapeError::TruncatedEscape(position))?;
+                let value = hex_digits(hex).ok_or(EscapeError::InvalidHex(position))?;
+                decoded.push(value as u8);
+                Ok(3)
+            }
+            Some(&c) => simple_escape(&[(b'\\', b'\\'), (b'"', b'"')], c, decoded)
+                .ok_or(EscapeError::InvalidEscape(position)),
+            None => Err(EscapeError::TruncatedEscape(position)),
+        }
+    }
+}
+
+impl EscapeDialect for Rust {
+    fn decode_escape(
+        &self,
+        rest: &[u8],
+        position: usize,
+        decoded: &mut Vec<u8>,
+    ) -> Result<usize, EscapeError> {
+        const SIMPLE: [(u8, u8); 7] = [
+            (b'n', b'\n'),
+            (b'r', b'\r'),
+            (b't', b'\t'),
+            (b'0', 0),
+            (b'\\', b'\\'),
+            (b'\'', b'\''),
+            (b'"', b'"'),
+        ];
+        match rest.first() {
+            Some(b'x') => {
+                let hex = rest
+                    .get(1..3)
+                    .ok_or(EscapeError::TruncatedEscape(position))?;
+                let value = hex_digits(hex).ok_or(EscapeError::InvalidHex(position))?;
+                if value > 0x7f {
+                    return Err(EscapeError::OutOfRange(position));
                 }
+                decoded.push(value as u8);
+                Ok(3)
             }
-            'x' => {
-                if escaping {
-                    if escaping_hex_remaining == 0 {
-                        // "\x"
-                        escaping_hex_remaining = 2;
-                    } else {
-                        // "\xx"
-                        escaping_hex_remaining -= 1;
-                        if escaping_hex_remaining == 0 {
-                            // "\xAx"
-                            escaping = false;
-                            values += 1;
-                        }
-                    }
-                } else {
-                    values += 1;
+            Some(b'u') => {
+                match rest.get(1) {
+                    Some(b'{') => {}
+                    Some(_) => return Err(EscapeError::InvalidEscape(position)),
+                    None => return Err(EscapeError::TruncatedEscape(position)),
+                }
+                let end = rest
+                    .iter()
+                    .position(|&c| c == b'}')
+                    .ok_or(EscapeError::TruncatedEscape(position))?;
+                let digits = rest[2..end]
+                    .iter()
+                    .copied()
+                    .filter(|&c| c != b'_')
+                    .collect::<Vec<_>>();
+                if digits.is_empty() || digits.len() > 6 || rest[2] == b'_' {
+                    return Err(EscapeError::InvalidHex(position));
                 }
+                let value = hex_digits(&digits).ok_or(EscapeError::InvalidHex(position))?;
+                let c = char::from_u32(value).ok_or(EscapeError::InvalidUnicode(position))?;
+                push_char(decoded, c);
+                Ok(end + 1)
             }
-            '\"' => {
-                if escaping {
-                    if escaping_hex_remaining > 0 {
-                        escaping_hex_remaining -= 1;
-                        if escaping_hex_remaining == 0 {
-                            // "\xA\""
-                            escaping = false;
-                            values += 1;
+            Some(&c) => {
+                simple_escape(&SIMPLE, c, decoded).ok_or(EscapeError::InvalidEscape(position))
+            }
+            None => Err(EscapeError::TruncatedEscape(position)),
+        }
+    }
+}
+
+impl EscapeDialect for Json {
+    fn decode_escape(
+        &self,
+        rest: &[u8],
+        position: usize,
+        decoded: &mut Vec<u8>,
+    ) -> Result<usize, EscapeError> {
+        const SIMPLE: [(u8, u8); 8] = [
+            (b'"', b'"'),
+            (b'\\', b'\\'),
+            (b'/', b'/'),
+            (b'b', 0x08),
+            (b'f', 0x0c),
+            (b'n', b'\n'),
+            (b'r', b'\r'),
+            (b't', b'\t'),
+        ];
+        let code_unit = |start: usize| {
+            let hex = rest
+                .get(start..start + 4)
+                .ok_or(EscapeError::TruncatedEscape(position))?;
+            hex_digits(hex).ok_or(EscapeError::InvalidHex(position))
+        };
+        match rest.first() {
+            Some(b'u') => {
+                let (value, len) = match code_unit(1)? {
+                    high @ 0xd800..=0xdbff => {
+                        if rest.get(5..7) != Some(b"\\u") {
+                            return Err(EscapeError::InvalidUnicode(position));
                         }
-                    } else {
-                        // "\\"
-                        escaping = false;
-                        values += 1;
+                        let low = code_unit(7)?;
+                        if !(0xdc00..=0xdfff).contains(&low) {
+                            return Err(EscapeError::InvalidUnicode(position));
+                        }
+                        (0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00), 11)
                     }
-                }
+                    0xdc00..=0xdfff => return Err(EscapeError::InvalidUnicode(position)),
+                    value => (value, 5),
+                };
+                push_char(decoded, char::from_u32(value).unwrap());
+                Ok(len)
+            }
+            Some(&c) => {
+                simple_escape(&SIMPLE, c, decoded).ok_or(EscapeError::InvalidEscape(position))
             }
-            _ => {
-                if escaping && escaping_hex_remaining > 0 {
-                    // "\xA"
-                    escaping_hex_remaining -= 1;
+            None => Err(EscapeError::TruncatedEscape(position)),
+        }
+    }
+}
+
+impl EscapeDialect for C {
+    fn decode_escape(
+        &self,
+        rest: &[u8],
+        position: usize,
+        decoded: &mut Vec<u8>,
+    ) -> Result<usize, EscapeError> {
+        const SIMPLE: [(u8, u8); 11] = [
+            (b'a', 0x07),
+            (b'b', 0x08),
+            (b'f', 0x0c),
+            (b'n', b'\n'),
+            (b'r', b'\r'),
+            (b't', b'\t'),
+            (b'v', 0x0b),
+            (b'\\', b'\\'),
+            (b'\'', b'\''),
+            (b'"', b'"'),
+            (b'?', b'?'),
+        ];
+        match rest.first() {
+            Some(b'0'..=b'7') => {
+                let len = rest
+                    .iter()
+                    .take(3)
+                    .take_while(|c| (b'0'..=b'7').contains(c))
+                    .count();
+                let value = rest[..len]
+                    .iter()
+                    .fold(0u32, |acc, &d| acc * 8 + (d - b'0') as u32);
+                if value > 0xff {
+                    return Err(EscapeError::OutOfRange(position));
                 }
-                if escaping_hex_remaining == 0 {
-                    // "\xAA"
-                    escaping = false;
-                    values += 1;
+                decoded.push(value as u8);
+                Ok(len)
+            }
+            Some(b'x') => {
+                let len = rest[1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .count();
+                if len == 0 {
+                    return Err(if rest.len() == 1 {
+                        EscapeError::TruncatedEscape(position)
+                    } else {
+                        EscapeError::InvalidHex(position)
+                    });
                 }
+                let value = hex_digits(&rest[1..1 + len])
+                    .filter(|&value| value <= 0xff)
+                    .ok_or(EscapeError::OutOfRange(position))?;
+                decoded.push(value as u8);
+                Ok(1 + len)
+            }
+            Some(&c) => {
+                simple_escape(&SIMPLE, c, decoded).ok_or(EscapeError::InvalidEscape(position))
             }
+            None => Err(EscapeError::TruncatedEscape(position)),
         }
-        literals += 1;
     }
+}
+
+fn dialect_from_name(name: &str) -> Option<&'static dyn EscapeDialect> {
+    match name {
+        "puzzle" => Some(&Puzzle),
+        "rust" => Some(&Rust),
+        "json" => Some(&Json),
+        "c" => Some(&C),
+        _ => None,
+    }
+}
 
-    (literals, values)
+fn decode_literal(input: &str) -> Result<Vec<u8>, EscapeError> {
+    decode_literal_with(&Puzzle, input)
+}
+
+fn decode_literal_with(dialect: &dyn EscapeDialect, input: &str) -> Result<Vec<u8>, EscapeError> {
+    let body = input
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(EscapeError::MissingQuotes)?
+        .as_bytes();
+
+    let mut decoded = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        // Offsets are reported relative to `input`, which includes the opening quote
+        let position = i + 1;
+        match body[i] {
+            b'"' => return Err(EscapeError::UnescapedQuote(position)),
+            b'\\' => i += 1 + dialect.decode_escape(&body[i + 1..], position, &mut decoded)?,
+            c => {
+                decoded.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    Ok(decoded)
+}
+
+fn encode_literal(value: &[u8]) -> String {
+    let mut encoded = String::from("\"");
+    for &c in value {
+        match c {
+            b'"' => encoded.push_str("\\\""),
+            b'\\' => encoded.push_str("\\\\"),
+            b' '..=b'~' => encoded.push(c as char),
+            _ => write!(encoded, "\\x{:02x}", c).unwrap(),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+fn count_chars_literals_values(input: &str) -> Result<(usize, usize), EscapeError> {
+    Ok((input.len(), decode_literal(input)?.len()))
+}
+
+fn count_chars_literals_values_with(
+    dialect: &dyn EscapeDialect,
+    input: &str,
+) -> Result<(usize, usize), EscapeError> {
+    Ok((input.len(), decode_literal_with(dialect, input)?.len()))
 }
 
 fn length_encoded_string(input: &str) -> usize {
-    dbg!(input);
-    input
-        .chars()
-        .map(|c| match c {
-            '\"' => 2,
-            '\\' => 2,
-            _ => 1,
-        })
-        .sum::<usize>()
-        + 2
+    encode_literal(input.as_bytes()).len()
 }
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    if let Some(name) = args.next() {
+        let dialect = dialect_from_name(&name).expect("unknown escape dialect");
+        let input = match args.next() {
+            Some(path) => std::fs::read_to_string(path).unwrap(),
+            None => include_str!("../input.txt").to_string(),
+        };
+        for line in input.lines() {
+            match count_chars_literals_values_with(dialect, line) {
+                Ok((code, memory)) => println!("{} {} {}", code, memory, line),
+                Err(error) => println!("{:?} {}", error, line),
+            }
+        }
+        return;
+    }
+
     let input = include_str!("../input.txt");
     let lines = input.lines();
 
     let encoded: usize = lines.clone().map(length_encoded_string).sum();
     let (literals, _values) = lines
         .map(|line| {
-            let (a, b) = count_chars_literals_values(line);
+            let (a, b) = count_chars_literals_values(line).unwrap();
             (a, b)
         })
         .reduce(|(a, b), (c, d)| (a + c, b + d))
@@ -107,7 +351,11 @@ mod test {
 
     #[test]
     fn test() {
-        assert_eq!(count_chars_literals_values("xxx"), (3, 3));
+        assert_eq!(count_chars_literals_values("\"xxx\""), Ok((5, 3)));
+        assert_eq!(
+            count_chars_literals_values("xxx"),
+            Err(EscapeError::MissingQuotes)
+        );
     }
 
     #[test]
@@ -116,7 +364,7 @@ mod test {
         let expected = [(2usize, 0usize), (5, 3), (10, 7), (6, 1)];
         let actual: Vec<_> = sample_input
             .iter()
-            .map(|s| count_chars_literals_values(s))
+            .map(|s| count_chars_literals_values(s).unwrap())
             .collect();
 
         expected
@@ -143,4 +391,157 @@ mod test {
             .zip(sample_input.iter().zip(expected_strings))
             .for_each(|((e, a), s)| assert_eq!(*e, a, "{:?}", s));
     }
+
+    #[test]
+    fn test_decode_literal() {
+        assert_eq!(decode_literal("\"a\\\\b\\\"c\""), Ok(b"a\\b\"c".to_vec()));
+        assert_eq!(decode_literal("\"\\x27\\xfF\""), Ok(vec![0x27, 0xff]));
+        assert_eq!(
+            decode_literal("\"\\x\""),
+            Err(EscapeError::TruncatedEscape(1))
+        );
+        assert_eq!(
+            decode_literal("\"a\\x4\""),
+            Err(EscapeError::TruncatedEscape(2))
+        );
+        assert_eq!(decode_literal("\"\\xg0\""), Err(EscapeError::InvalidHex(1)));
+        assert_eq!(decode_literal("\"\\x+f\""), Err(EscapeError::InvalidHex(1)));
+        assert_eq!(
+            decode_literal("\"\\n\""),
+            Err(EscapeError::InvalidEscape(1))
+        );
+        assert_eq!(
+            decode_literal("\"\\\""),
+            Err(EscapeError::TruncatedEscape(1))
+        );
+        assert_eq!(
+            decode_literal("\"a\"b\""),
+            Err(EscapeError::UnescapedQuote(2))
+        );
+        assert_eq!(decode_literal("\""), Err(EscapeError::MissingQuotes));
+    }
+
+    #[test]
+    fn test_encode_literal() {
+        assert_eq!(encode_literal(b""), "\"\"");
+        assert_eq!(encode_literal(b"a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(
+            encode_literal(&[0x00, b'~', 0x7f, 0xff]),
+            "\"\\x00~\\x7f\\xff\""
+        );
+    }
+
+    #[test]
+    fn test_rust_dialect() {
+        let count = |s| count_chars_literals_values_with(&Rust, s);
+        assert_eq!(count(r#""a\n\t\0\'\"""#), Ok((13, 6)));
+        assert_eq!(count(r#""\x41\u{e9}\u{1F6_00}""#), Ok((22, 7)));
+        assert_eq!(count(r#""\x80""#), Err(EscapeError::OutOfRange(1)));
+        assert_eq!(count(r#""\u{d800}""#), Err(EscapeError::InvalidUnicode(1)));
+        assert_eq!(count(r#""\u{}""#), Err(EscapeError::InvalidHex(1)));
+        assert_eq!(count(r#""\u{1234567}""#), Err(EscapeError::InvalidHex(1)));
+        assert_eq!(count(r#""\u{41""#), Err(EscapeError::TruncatedEscape(1)));
+        assert_eq!(count(r#""\u41""#), Err(EscapeError::InvalidEscape(1)));
+    }
+
+    #[test]
+    fn test_json_dialect() {
+        let count = |s| count_chars_literals_values_with(&Json, s);
+        assert_eq!(count(r#""\/\b\f\u0041\u00e9""#), Ok((20, 6)));
+        assert_eq!(
+            decode_literal_with(&Json, r#""\ud83d\ude00""#),
+            Ok("\u{1f600}".as_bytes().to_vec())
+        );
+        assert_eq!(count(r#""\ud83d""#), Err(EscapeError::InvalidUnicode(1)));
+        assert_eq!(
+            count(r#""\ud83d\u0041""#),
+            Err(EscapeError::InvalidUnicode(1))
+        );
+        assert_eq!(count(r#""\ude00""#), Err(EscapeError::InvalidUnicode(1)));
+        assert_eq!(count(r#""\u00e""#), Err(EscapeError::TruncatedEscape(1)));
+        assert_eq!(count(r#""\u00eg""#), Err(EscapeError::InvalidHex(1)));
+        assert_eq!(count(r#""\x41""#), Err(EscapeError::InvalidEscape(1)));
+    }
+
+    #[test]
+    fn test_c_dialect() {
+        let count = |s| count_chars_literals_values_with(&C, s);
+        assert_eq!(count(r#""\a\v\?\0\101\1234""#), Ok((19, 7)));
+        assert_eq!(
+            decode_literal_with(&C, r#""\x41\xfg""#),
+            Ok(b"A\x0fg".to_vec())
+        );
+        assert_eq!(count(r#""\400""#), Err(EscapeError::OutOfRange(1)));
+        assert_eq!(count(r#""\x100""#), Err(EscapeError::OutOfRange(1)));
+        assert_eq!(count(r#""\xg""#), Err(EscapeError::InvalidHex(1)));
+        assert_eq!(count(r#""\x""#), Err(EscapeError::TruncatedEscape(1)));
+        assert_eq!(count(r#""\u0041""#), Err(EscapeError::InvalidEscape(1)));
+    }
+
+    mod properties {
+        use proptest::prelude::*;
+
+        use super::super::*;
+
+        // Bias generated bytes towards the characters involved in escapes so that
+        // sequences like `\\x27` (an escaped backslash followed by `x27`) show up often.
+        fn tricky_bytes() -> impl Strategy<Value = Vec<u8>> {
+            prop::collection::vec(
+                prop_oneof![any::<u8>(), prop::sample::select(b"\\\"x0aF".to_vec())],
+                0..64,
+            )
+        }
+
+        fn escape_token() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "[ -!#-\\[\\]-~]",
+                Just(String::from("\\\\")),
+                Just(String::from("\\\"")),
+                "\\\\x[0-9a-fA-F]{2}",
+            ]
+        }
+
+        proptest! {
+            #[test]
+            fn round_trip(value in tricky_bytes()) {
+                let encoded = encode_literal(&value);
+                prop_assert_eq!(decode_literal(&encoded), Ok(value));
+            }
+
+            #[test]
+            fn code_length_at_least_memory_length(value in tricky_bytes()) {
+                let literal = encode_literal(&value);
+                let (code, memory) = count_chars_literals_values(&literal).unwrap();
+                prop_assert!(code >= memory);
+                prop_assert_eq!(memory, value.len());
+            }
+
+            #[test]
+            fn encoded_length(value in tricky_bytes()) {
+                let literal = encode_literal(&value);
+                let escapes = literal.bytes().filter(|c| [b'"', b'\\'].contains(c)).count();
+                prop_assert_eq!(length_encoded_string(&literal), literal.len() + escapes + 2);
+            }
+
+            #[test]
+            fn one_value_per_token(tokens in prop::collection::vec(escape_token(), 0..32)) {
+                let literal = format!("\"{}\"", tokens.concat());
+                let (code, memory) = count_chars_literals_values(&literal).unwrap();
+                prop_assert_eq!(code, tokens.concat().len() + 2);
+                prop_assert_eq!(memory, tokens.len());
+            }
+
+            #[test]
+            fn truncated_literals_are_errors(value in tricky_bytes(), cut in 1usize..4) {
+                let literal = encode_literal(&[value.as_slice(), &[0xff]].concat());
+                let truncated = format!("{}\"", &literal[..literal.len() - 1 - cut]);
+                prop_assert!(decode_literal(&truncated).is_err());
+            }
+
+            #[test]
+            fn decode_never_panics(input in "\"[\\\\\"x0-9a-z]*\"") {
+                let _ = decode_literal(&input);
+            }
+        }
+    }
 }
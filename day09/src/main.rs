@@ -1,4 +1,10 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::BTreeSet;
+#[cfg(test)]
+use std::collections::HashSet;
+#[cfg(test)]
+use std::ops::AddAssign;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use nom::{
     bytes::complete::tag,
@@ -6,7 +12,7 @@ use nom::{
     IResult,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Distance {
     from: String,
     to: String,
@@ -36,29 +42,133 @@ fn parse_input(input: &str) -> Vec<Distance> {
         .collect()
 }
 
-type Route = Vec<String>;
+type LocationId = u16;
+
+// The searches keep the locations left to visit in a `usize` bitmask
+#[cfg(test)]
+const MAX_SEARCH_LOCATIONS: usize = usize::BITS as usize - 1;
+
+// Held-Karp keeps a table entry per subset of locations and last location. At
+// this size that is already a few hundred megabytes, doubling with each location.
+const MAX_HELD_KARP_LOCATIONS: usize = 20;
+
+type Route = Vec<LocationId>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Objective {
+    Shortest,
+    Longest,
+}
+
+impl Objective {
+    fn improves(self, candidate: usize, incumbent: usize) -> bool {
+        match self {
+            Objective::Shortest => candidate < incumbent,
+            Objective::Longest => candidate > incumbent,
+        }
+    }
+
+    // Picks the better of two optional routes, preferring the lexicographically
+    // smallest route on ties.
+    #[cfg(test)]
+    fn better(
+        self,
+        a: Option<(Route, usize)>,
+        b: Option<(Route, usize)>,
+    ) -> Option<(Route, usize)> {
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                if self.improves(b.1, a.1) || (a.1 == b.1 && b.0 < a.0) {
+                    Some(b)
+                } else {
+                    Some(a)
+                }
+            }
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+// Closed tours return to their first location, which is then repeated at the
+// end of the route. `precedences` lists `(before, after)` pairs.
+#[derive(Debug, Clone, Default)]
+struct RouteConstraints {
+    closed: bool,
+    start: Option<LocationId>,
+    end: Option<LocationId>,
+    precedences: Vec<(LocationId, LocationId)>,
+}
+
+// The best `k` routes in order, and how many distinct routes tie for the
+// optimum (which may be more than `k`). Reversed routes are only counted once
+// when distances are symmetric.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RankedRoutes {
+    routes: Vec<(Route, usize)>,
+    optimum_count: usize,
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SearchStats {
+    tasks: usize,
+    nodes: usize,
+    pruned: usize,
+    routes: usize,
+    improvements: usize,
+}
+
+#[cfg(test)]
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.tasks += other.tasks;
+        self.nodes += other.nodes;
+        self.pruned += other.pruned;
+        self.routes += other.routes;
+        self.improvements += other.improvements;
+    }
+}
+
+// Optimistic bound on the final length of a partial route: the remaining
+// locations add nothing when minimizing, and their longest incoming edge when
+// maximizing.
+#[cfg(test)]
+fn bound(objective: Objective, length: usize, remaining: usize, longest_into: &[usize]) -> usize {
+    match objective {
+        Objective::Shortest => length,
+        Objective::Longest => {
+            length
+                + (0..longest_into.len())
+                    .filter(|l| remaining & (1 << l) != 0)
+                    .map(|l| longest_into[l])
+                    .sum::<usize>()
+        }
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Distances {
+    // Sorted, so that a location's id is its position
     locations: Vec<String>,
-    map: HashMap<(String, String), usize>,
+    matrix: Vec<Option<usize>>,
+    #[cfg(test)]
+    directed: bool,
 }
 
 impl Distances {
-    fn compute_key(distance: (&String, &String)) -> (String, String) {
-        let (from, to) = distance;
-        (
-            std::cmp::min(from, to).clone(),
-            std::cmp::max(from, to).clone(),
-        )
+    pub fn new(distances: Vec<Distance>) -> Self {
+        Distances::build(distances, false)
     }
 
-    pub fn get(&self, distance: (&String, &String)) -> Option<usize> {
-        let key = Distances::compute_key(distance);
-        self.map.get(&key).copied()
+    // Unlike `new`, `A to B` only describes travelling from A to B, so the
+    // opposite direction needs its own line to be usable.
+    #[cfg(test)]
+    pub fn directed(distances: Vec<Distance>) -> Self {
+        Distances::build(distances, true)
     }
 
-    pub fn new(distances: Vec<Distance>) -> Self {
+    fn build(distances: Vec<Distance>, directed: bool) -> Self {
         let locations = distances
             .iter()
             .flat_map(
@@ -72,113 +182,534 @@ impl Distances {
             .into_iter()
             .cloned()
             .collect::<Vec<_>>();
+        assert!(locations.len() <= LocationId::MAX as usize);
 
-        let mut map = HashMap::new();
-        distances
-            .into_iter()
-            .for_each(|Distance { from, to, distance }| {
-                let key = Distances::compute_key((&from, &to));
-                map.insert(key, distance);
-            });
+        let n = locations.len();
+        let mut matrix = vec![None; n * n];
+        for id in 0..n {
+            matrix[id * n + id] = Some(0);
+        }
+        let id = |name: &str| {
+            locations
+                .binary_search_by(|l| l.as_str().cmp(name))
+                .unwrap()
+        };
+        for Distance { from, to, distance } in distances {
+            let (from, to) = (id(&from), id(&to));
+            matrix[from * n + to] = Some(distance);
+            if !directed {
+                matrix[to * n + from] = Some(distance);
+            }
+        }
 
-        Distances { locations, map }
+        Distances {
+            locations,
+            matrix,
+            #[cfg(test)]
+            directed,
+        }
     }
 
-    pub fn route_length(&self, route: &Route) -> usize {
-        route
-            .iter()
-            .zip(route.iter().skip(1))
-            .map(|(l, r)| self.get((l, r)).unwrap())
-            .sum()
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn location_ids(&self) -> impl Iterator<Item = LocationId> {
+        0..self.len() as LocationId
     }
 
-    pub fn shortest_route(self) -> (Route, usize) {
-        let mut best_route: Route = self.locations.clone().into_iter().collect();
-        let mut best_route_length = self.route_length(&best_route);
-        let mut stack: Vec<(usize, Route, HashSet<String>)> =
-            vec![(0, vec![], self.locations.iter().cloned().collect())];
+    #[cfg(test)]
+    pub fn id(&self, name: &str) -> Option<LocationId> {
+        self.locations
+            .binary_search_by(|l| l.as_str().cmp(name))
+            .ok()
+            .map(|id| id as LocationId)
+    }
 
-        let mut steps = 0;
+    #[cfg(test)]
+    pub fn name(&self, id: LocationId) -> &str {
+        &self.locations[id as usize]
+    }
 
-        while let Some((current_route_length, current_route, remaining)) = stack.pop() {
-            steps += 1;
+    #[cfg(test)]
+    pub fn route_names(&self, route: &[LocationId]) -> Vec<&str> {
+        route.iter().map(|&id| self.name(id)).collect()
+    }
+
+    pub fn distance(&self, from: LocationId, to: LocationId) -> Option<usize> {
+        self.matrix[from as usize * self.len() + to as usize]
+    }
 
+    #[cfg(test)]
+    pub fn get(&self, (from, to): (&str, &str)) -> Option<usize> {
+        self.distance(self.id(from)?, self.id(to)?)
+    }
+
+    #[cfg(test)]
+    pub fn route_length(&self, route: &[LocationId]) -> Option<usize> {
+        route.windows(2).map(|w| self.distance(w[0], w[1])).sum()
+    }
+
+    #[cfg(test)]
+    pub fn shortest_route(self) -> Option<(Route, usize)> {
+        let mut best: Option<(Route, usize)> = None;
+        let mut stack: Vec<(usize, Route, HashSet<LocationId>)> =
+            vec![(0, vec![], self.location_ids().collect())];
+
+        while let Some((current_route_length, current_route, remaining)) = stack.pop() {
             // Upper bound
-            if current_route_length > best_route_length {
+            if matches!(&best, Some((_, best_length)) if current_route_length > *best_length) {
                 continue;
             }
 
             if remaining.is_empty() {
-                if current_route_length < best_route_length {
-                    best_route = current_route;
-                    best_route_length = current_route_length;
+                if !matches!(&best, Some((_, best_length)) if current_route_length >= *best_length)
+                {
+                    best = Some((current_route, current_route_length));
                 }
             } else {
                 for remaining_location in remaining.iter() {
-                    let mut new_route = current_route.clone();
-                    let last_location = new_route.last();
+                    let new_route_length = match current_route.last() {
+                        Some(last_location) => {
+                            match self.distance(*last_location, *remaining_location) {
+                                Some(distance) => current_route_length + distance,
+                                None => continue,
+                            }
+                        }
+                        None => 0,
+                    };
 
                     let mut new_remaining = remaining.clone();
                     new_remaining.remove(remaining_location);
 
-                    let new_route_length = current_route_length
-                        + if let Some(last_location) = last_location {
-                            self.get((last_location, remaining_location)).unwrap()
-                        } else {
-                            0
-                        };
+                    let mut new_route = current_route.clone();
+                    new_route.push(*remaining_location);
+                    stack.push((new_route_length, new_route, new_remaining));
+                }
+            }
+        }
+
+        best
+    }
+
+    #[cfg(test)]
+    pub fn longest_route(self) -> Option<(Route, usize)> {
+        let mut best: Option<(Route, usize)> = None;
+        let mut stack: Vec<(usize, Route, HashSet<LocationId>)> =
+            vec![(0, vec![], self.location_ids().collect())];
+
+        while let Some((current_route_length, current_route, remaining)) = stack.pop() {
+            if remaining.is_empty() {
+                if !matches!(&best, Some((_, best_length)) if current_route_length <= *best_length)
+                {
+                    best = Some((current_route, current_route_length));
+                }
+            } else {
+                for remaining_location in remaining.iter() {
+                    let new_route_length = match current_route.last() {
+                        Some(last_location) => {
+                            match self.distance(*last_location, *remaining_location) {
+                                Some(distance) => current_route_length + distance,
+                                None => continue,
+                            }
+                        }
+                        None => 0,
+                    };
+
+                    let mut new_remaining = remaining.clone();
+                    new_remaining.remove(remaining_location);
 
-                    new_route.push(remaining_location.clone());
+                    let mut new_route = current_route.clone();
+                    new_route.push(*remaining_location);
                     stack.push((new_route_length, new_route, new_remaining));
                 }
             }
         }
 
-        // dbg!(steps);
+        best
+    }
+
+    #[cfg(test)]
+    fn longest_into(&self) -> Vec<usize> {
+        self.location_ids()
+            .map(|to| {
+                self.location_ids()
+                    .filter_map(|from| self.distance(from, to))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    // Branch-and-bound from `prefix` that prunes against a best-so-far length
+    // shared with other searches. Ties are broken towards the lexicographically
+    // smallest route so that the result does not depend on how work is split.
+    #[cfg(test)]
+    fn search_from(
+        &self,
+        objective: Objective,
+        prefix: (usize, Route, usize),
+        incumbent: &AtomicUsize,
+        longest_into: &[usize],
+        stats: &mut SearchStats,
+    ) -> Option<(Route, usize)> {
+        let n = self.len();
+        let mut best: Option<(Route, usize)> = None;
+        let mut stack = vec![prefix];
+
+        while let Some((current_route_length, current_route, remaining)) = stack.pop() {
+            stats.nodes += 1;
+
+            let bound = bound(objective, current_route_length, remaining, longest_into);
+            if objective.improves(incumbent.load(Ordering::Relaxed), bound) {
+                stats.pruned += 1;
+                continue;
+            }
+
+            if remaining == 0 {
+                stats.routes += 1;
+                let better = match &best {
+                    None => true,
+                    Some((best_route, best_length)) => {
+                        objective.improves(current_route_length, *best_length)
+                            || (current_route_length == *best_length && current_route < *best_route)
+                    }
+                };
+                if better {
+                    stats.improvements += 1;
+                    match objective {
+                        Objective::Shortest => {
+                            incumbent.fetch_min(current_route_length, Ordering::Relaxed)
+                        }
+                        Objective::Longest => {
+                            incumbent.fetch_max(current_route_length, Ordering::Relaxed)
+                        }
+                    };
+                    best = Some((current_route, current_route_length));
+                }
+            } else {
+                for next in (0..n).filter(|l| remaining & (1 << l) != 0) {
+                    let next = next as LocationId;
+                    let new_route_length = match current_route.last() {
+                        Some(&last) => match self.distance(last, next) {
+                            Some(distance) => current_route_length + distance,
+                            None => continue,
+                        },
+                        None => 0,
+                    };
+
+                    let mut new_route = current_route.clone();
+                    new_route.push(next);
+                    stack.push((new_route_length, new_route, remaining & !(1 << next)));
+                }
+            }
+        }
+
+        best
+    }
+
+    // Every location as a bitmask, for the searches
+    #[cfg(test)]
+    fn all_locations(&self) -> usize {
+        assert!(
+            self.len() <= MAX_SEARCH_LOCATIONS,
+            "{} locations are too many to search, the limit is {}",
+            self.len(),
+            MAX_SEARCH_LOCATIONS
+        );
+        (1usize << self.len()) - 1
+    }
+
+    #[cfg(test)]
+    fn initial_incumbent(objective: Objective) -> AtomicUsize {
+        AtomicUsize::new(match objective {
+            Objective::Shortest => usize::MAX,
+            Objective::Longest => 0,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn branch_and_bound(&self, objective: Objective) -> (Option<(Route, usize)>, SearchStats) {
+        let mut stats = SearchStats::default();
+        let all = self.all_locations();
+        let best = self.search_from(
+            objective,
+            (0, vec![], all),
+            &Distances::initial_incumbent(objective),
+            &self.longest_into(),
+            &mut stats,
+        );
+        (best, stats)
+    }
+
+    // Splits the search into one task per valid (first, second) location pair,
+    // which worker threads pick up from a shared queue.
+    #[cfg(test)]
+    pub fn parallel_branch_and_bound(
+        &self,
+        objective: Objective,
+        threads: usize,
+    ) -> (Option<(Route, usize)>, SearchStats) {
+        let n = self.len();
+        let all = self.all_locations();
+        let tasks = if n < 2 {
+            vec![(0, vec![], all)]
+        } else {
+            self.location_ids()
+                .flat_map(|first| self.location_ids().map(move |second| (first, second)))
+                .filter_map(|(first, second)| {
+                    let distance = if first == second {
+                        None
+                    } else {
+                        self.distance(first, second)
+                    }?;
+                    Some((
+                        distance,
+                        vec![first, second],
+                        all & !(1 << first) & !(1 << second),
+                    ))
+                })
+                .collect()
+        };
+
+        let incumbent = Distances::initial_incumbent(objective);
+        let longest_into = self.longest_into();
+        let next_task = AtomicUsize::new(0);
 
-        (best_route, best_route_length)
+        let results = std::thread::scope(|scope| {
+            let workers = (0..threads.max(1))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut stats = SearchStats::default();
+                        let mut best: Option<(Route, usize)> = None;
+                        while let Some(task) = tasks.get(next_task.fetch_add(1, Ordering::Relaxed))
+                        {
+                            stats.tasks += 1;
+                            let found = self.search_from(
+                                objective,
+                                task.clone(),
+                                &incumbent,
+                                &longest_into,
+                                &mut stats,
+                            );
+                            best = objective.better(best, found);
+                        }
+                        (best, stats)
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let mut stats = SearchStats::default();
+        let mut best: Option<(Route, usize)> = None;
+        for (found, worker_stats) in results {
+            stats += worker_stats;
+            best = objective.better(best, found);
+        }
+        (best, stats)
     }
 
-    pub fn longest_route(self) -> (Route, usize) {
-        let mut best_route: Route = self.locations.clone().into_iter().collect();
-        let mut best_route_length = self.route_length(&best_route);
-        let mut stack: Vec<(usize, Route, HashSet<String>)> =
-            vec![(0, vec![], self.locations.iter().cloned().collect())];
+    #[cfg(test)]
+    pub fn top_routes(&self, objective: Objective, k: usize) -> RankedRoutes {
+        let n = self.len();
+        let mut ranked = RankedRoutes {
+            routes: vec![],
+            optimum_count: 0,
+        };
+        let mut optimum: Option<usize> = None;
+
+        let longest_into = self.longest_into();
 
-        let mut steps = 0;
+        let all = self.all_locations();
+        let mut stack: Vec<(usize, Route, usize)> = vec![(0, vec![], all)];
 
         while let Some((current_route_length, current_route, remaining)) = stack.pop() {
-            steps += 1;
+            let bound = bound(objective, current_route_length, remaining, &longest_into);
+            // Only prune what is strictly worse than the k-th route so that every
+            // route tying with the optimum is still counted.
+            let threshold = if ranked.routes.len() < k {
+                None
+            } else {
+                ranked.routes.last().map(|(_, length)| *length).or(optimum)
+            };
+            if threshold.is_some_and(|threshold| objective.improves(threshold, bound)) {
+                continue;
+            }
 
-            if remaining.is_empty() {
-                if current_route_length > best_route_length {
-                    best_route = current_route;
-                    best_route_length = current_route_length;
+            if remaining == 0 {
+                if !self.directed && current_route.first() > current_route.last() {
+                    continue;
+                }
+
+                match optimum {
+                    Some(best) if best == current_route_length => ranked.optimum_count += 1,
+                    Some(best) if !objective.improves(current_route_length, best) => {}
+                    _ => {
+                        optimum = Some(current_route_length);
+                        ranked.optimum_count = 1;
+                    }
+                }
+
+                let position = ranked
+                    .routes
+                    .iter()
+                    .position(|(route, length)| {
+                        objective.improves(current_route_length, *length)
+                            || (current_route_length == *length && current_route < *route)
+                    })
+                    .unwrap_or(ranked.routes.len());
+                if position < k {
+                    ranked
+                        .routes
+                        .insert(position, (current_route, current_route_length));
+                    ranked.routes.truncate(k);
                 }
             } else {
-                for remaining_location in remaining.iter() {
+                for next in (0..n).filter(|l| remaining & (1 << l) != 0) {
+                    let next = next as LocationId;
+                    let new_route_length = match current_route.last() {
+                        Some(&last) => match self.distance(last, next) {
+                            Some(distance) => current_route_length + distance,
+                            None => continue,
+                        },
+                        None => 0,
+                    };
+
                     let mut new_route = current_route.clone();
-                    let last_location = new_route.last();
+                    new_route.push(next);
+                    stack.push((new_route_length, new_route, remaining & !(1 << next)));
+                }
+            }
+        }
 
-                    let mut new_remaining = remaining.clone();
-                    new_remaining.remove(remaining_location);
+        ranked
+    }
+
+    pub fn held_karp(&self, objective: Objective) -> Option<(Route, usize)> {
+        self.constrained_route(objective, &RouteConstraints::default())
+    }
 
-                    let new_route_length = current_route_length
-                        + if let Some(last_location) = last_location {
-                            self.get((last_location, remaining_location)).unwrap()
-                        } else {
-                            0
-                        };
+    // Held-Karp over subsets of location ids: `best[mask * n + last]` is the
+    // optimal length of a path visiting exactly `mask` and ending at `last`.
+    // Returns `None` when no route visits every location under `constraints`.
+    pub fn constrained_route(
+        &self,
+        objective: Objective,
+        constraints: &RouteConstraints,
+    ) -> Option<(Route, usize)> {
+        const UNREACHABLE: usize = usize::MAX;
+        const NO_PARENT: LocationId = LocationId::MAX;
 
-                    new_route.push(remaining_location.clone());
-                    stack.push((new_route_length, new_route, new_remaining));
+        let n = self.len();
+        if n == 0 {
+            return Some((vec![], 0));
+        }
+        assert!(
+            n <= MAX_HELD_KARP_LOCATIONS,
+            "{} locations are too many for Held-Karp, the limit is {}",
+            n,
+            MAX_HELD_KARP_LOCATIONS
+        );
+
+        // The closing edge depends on where the tour starts, so a closed tour is
+        // solved once per start. Without precedences or a fixed end every
+        // rotation of a tour is equivalent and starting anywhere will do.
+        if constraints.closed && constraints.start.is_none() {
+            let starts = if constraints.precedences.is_empty() && constraints.end.is_none() {
+                vec![0]
+            } else {
+                self.location_ids().collect()
+            };
+            return starts
+                .into_iter()
+                .filter_map(|start| {
+                    let constraints = RouteConstraints {
+                        start: Some(start),
+                        ..constraints.clone()
+                    };
+                    self.constrained_route(objective, &constraints)
+                })
+                .reduce(|a, b| if objective.improves(b.1, a.1) { b } else { a });
+        }
+
+        let mut predecessors = vec![0usize; n];
+        for &(before, after) in &constraints.precedences {
+            predecessors[after as usize] |= 1 << before;
+        }
+
+        let states = 1 << n;
+        let full = states - 1;
+        let mut best = vec![UNREACHABLE; states * n];
+        let mut parent = vec![NO_PARENT; states * n];
+        for start in 0..n {
+            let allowed = constraints.start.is_none_or(|s| s as usize == start);
+            if allowed && predecessors[start] == 0 {
+                best[(1 << start) * n + start] = 0;
+            }
+        }
+
+        for mask in 1..states {
+            for last in 0..n {
+                let length = best[mask * n + last];
+                if length == UNREACHABLE {
+                    continue;
+                }
+                for next in (0..n).filter(|next| mask & (1 << next) == 0) {
+                    let next_mask = mask | 1 << next;
+                    if predecessors[next] & !mask != 0
+                        || (constraints.end == Some(next as LocationId) && next_mask != full)
+                    {
+                        continue;
+                    }
+                    let Some(distance) = self.distance(last as LocationId, next as LocationId)
+                    else {
+                        continue;
+                    };
+                    let state = next_mask * n + next;
+                    let candidate = length + distance;
+                    if best[state] == UNREACHABLE || objective.improves(candidate, best[state]) {
+                        best[state] = candidate;
+                        parent[state] = last as LocationId;
+                    }
                 }
             }
         }
 
-        // dbg!(steps);
+        let (mut last, length) = (0..n)
+            .filter(|&last| best[full * n + last] != UNREACHABLE)
+            .filter(|&last| constraints.end.is_none_or(|end| end as usize == last))
+            .filter_map(|last| {
+                let closing = match constraints.start {
+                    Some(start) if constraints.closed => {
+                        self.distance(last as LocationId, start)?
+                    }
+                    _ => 0,
+                };
+                Some((last, best[full * n + last] + closing))
+            })
+            .reduce(|a, b| if objective.improves(b.1, a.1) { b } else { a })?;
+
+        let mut route = Vec::with_capacity(n + 1);
+        let mut mask = full;
+        loop {
+            route.push(last as LocationId);
+            let previous = parent[mask * n + last];
+            if previous == NO_PARENT {
+                break;
+            }
+            mask &= !(1 << last);
+            last = previous as usize;
+        }
+        route.reverse();
+        if constraints.closed {
+            route.push(route[0]);
+        }
 
-        (best_route, best_route_length)
+        Some((route, length))
     }
 }
 
@@ -186,8 +717,13 @@ fn main() {
     let input = include_str!("../input.txt");
     let distances_vec = parse_input(input);
     let distances = Distances::new(distances_vec);
-    let longest_route = distances.longest_route();
-    println!("{}", longest_route.1);
+
+    for objective in [Objective::Shortest, Objective::Longest] {
+        match distances.held_karp(objective) {
+            Some((_, length)) => println!("{}", length),
+            None => println!("no route visits every location"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -211,4 +747,427 @@ mod tests {
         let distances = Distances::new(distances_vec);
         dbg!(distances.shortest_route());
     }
+
+    #[test]
+    fn test_held_karp_sample() {
+        let input = include_str!("../sample.txt");
+        let distances = Distances::new(parse_input(input));
+
+        let (shortest, shortest_length) = distances.held_karp(Objective::Shortest).unwrap();
+        assert_eq!(shortest_length, 605);
+        assert_eq!(distances.route_length(&shortest), Some(605));
+
+        let (longest, longest_length) = distances.held_karp(Objective::Longest).unwrap();
+        assert_eq!(longest_length, 982);
+        assert_eq!(distances.route_length(&longest), Some(982));
+    }
+
+    #[test]
+    fn test_missing_edges() {
+        let input = "A to B = 1\nB to C = 2\nC to D = 3\nA to D = 10";
+        let distances = Distances::new(parse_input(input));
+        assert_eq!(distances.get(("A", "C")), None);
+
+        let (route, length) = distances.held_karp(Objective::Shortest).unwrap();
+        assert_eq!(length, 6);
+        assert_eq!(distances.route_names(&route).len(), 4);
+        assert_eq!(distances.held_karp(Objective::Longest).unwrap().1, 15);
+
+        let input = "A to C = 1\nB to C = 2\nD to C = 3";
+        let distances = Distances::new(parse_input(input));
+        assert_eq!(distances.held_karp(Objective::Shortest), None);
+        assert_eq!(distances.held_karp(Objective::Longest), None);
+        assert_eq!(distances.shortest_route(), None);
+    }
+
+    #[test]
+    fn test_directed() {
+        let input = "A to B = 1\nB to A = 100\nB to C = 5\nC to A = 7";
+        let distances = Distances::directed(parse_input(input));
+        assert_eq!(distances.get(("A", "B")), Some(1));
+        assert_eq!(distances.get(("B", "A")), Some(100));
+        assert_eq!(distances.get(("C", "B")), None);
+
+        let (route, length) = distances.held_karp(Objective::Shortest).unwrap();
+        assert_eq!(distances.route_names(&route), ["A", "B", "C"]);
+        assert_eq!(length, 6);
+
+        let (route, length) = distances.held_karp(Objective::Longest).unwrap();
+        assert_eq!(distances.route_names(&route), ["B", "C", "A"]);
+        assert_eq!(length, 12);
+    }
+
+    #[test]
+    fn test_name_id_mapping() {
+        let input = include_str!("../sample.txt");
+        let distances = Distances::new(parse_input(input));
+
+        assert_eq!(distances.len(), 3);
+        for id in distances.location_ids() {
+            assert_eq!(distances.id(distances.name(id)), Some(id));
+        }
+        assert_eq!(distances.id("Paris"), None);
+        assert_eq!(distances.get(("Dublin", "London")), Some(464));
+        assert_eq!(distances.get(("London", "Dublin")), Some(464));
+        assert_eq!(distances.get(("London", "Paris")), None);
+
+        let (route, _) = distances.held_karp(Objective::Shortest).unwrap();
+        let names = distances.route_names(&route);
+        assert!(
+            names == ["London", "Dublin", "Belfast"] || names == ["Belfast", "Dublin", "London"]
+        );
+    }
+
+    fn permutations(
+        route: &mut Route,
+        remaining: &mut Vec<LocationId>,
+        f: &mut impl FnMut(&Route),
+    ) {
+        if remaining.is_empty() {
+            f(route);
+        }
+        for i in 0..remaining.len() {
+            let location = remaining.remove(i);
+            route.push(location);
+            permutations(route, remaining, f);
+            route.pop();
+            remaining.insert(i, location);
+        }
+    }
+
+    // Exhaustive reference for `constrained_route`, trying every permutation.
+    fn brute_force_route(
+        distances: &Distances,
+        objective: Objective,
+        constraints: &RouteConstraints,
+    ) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        permutations(
+            &mut vec![],
+            &mut distances.location_ids().collect(),
+            &mut |route| {
+                let position = |id| route.iter().position(|&l| l == id).unwrap();
+                let satisfied = constraints.start.is_none_or(|s| route[0] == s)
+                    && constraints.end.is_none_or(|e| route[route.len() - 1] == e)
+                    && constraints
+                        .precedences
+                        .iter()
+                        .all(|&(before, after)| position(before) < position(after));
+                let mut route = route.clone();
+                if constraints.closed {
+                    route.push(route[0]);
+                }
+                if let (true, Some(length)) = (satisfied, distances.route_length(&route)) {
+                    if best.is_none_or(|best| objective.improves(length, best)) {
+                        best = Some(length);
+                    }
+                }
+            },
+        );
+        best
+    }
+
+    #[test]
+    fn test_constrained_route_sample() {
+        let input = include_str!("../sample.txt");
+        let distances = Distances::new(parse_input(input));
+        let [belfast, dublin, london] =
+            ["Belfast", "Dublin", "London"].map(|n| distances.id(n).unwrap());
+
+        let closed = RouteConstraints {
+            closed: true,
+            ..Default::default()
+        };
+        let (route, length) = distances
+            .constrained_route(Objective::Shortest, &closed)
+            .unwrap();
+        assert_eq!(length, 464 + 518 + 141);
+        assert_eq!(route.len(), 4);
+        assert_eq!(route[0], route[3]);
+        assert_eq!(distances.route_length(&route), Some(length));
+
+        let from_belfast = RouteConstraints {
+            start: Some(belfast),
+            ..Default::default()
+        };
+        let (route, length) = distances
+            .constrained_route(Objective::Shortest, &from_belfast)
+            .unwrap();
+        assert_eq!(route, [belfast, dublin, london]);
+        assert_eq!(length, 605);
+
+        let to_dublin = RouteConstraints {
+            end: Some(dublin),
+            ..Default::default()
+        };
+        assert_eq!(
+            distances
+                .constrained_route(Objective::Longest, &to_dublin)
+                .unwrap()
+                .1,
+            982
+        );
+
+        let london_before_belfast = RouteConstraints {
+            precedences: vec![(london, belfast)],
+            ..Default::default()
+        };
+        let (route, _) = distances
+            .constrained_route(Objective::Shortest, &london_before_belfast)
+            .unwrap();
+        assert_eq!(route, [london, dublin, belfast]);
+
+        let impossible = RouteConstraints {
+            start: Some(dublin),
+            precedences: vec![(london, dublin)],
+            ..Default::default()
+        };
+        assert_eq!(
+            distances.constrained_route(Objective::Shortest, &impossible),
+            None
+        );
+    }
+
+    #[test]
+    fn test_top_routes_sample() {
+        let input = include_str!("../sample.txt");
+        let distances = Distances::new(parse_input(input));
+
+        let shortest = distances.top_routes(Objective::Shortest, 10);
+        let lengths = shortest.routes.iter().map(|(_, l)| *l).collect::<Vec<_>>();
+        assert_eq!(lengths, [605, 659, 982]);
+        assert_eq!(shortest.optimum_count, 1);
+
+        let longest = distances.top_routes(Objective::Longest, 1);
+        assert_eq!(longest.routes.len(), 1);
+        assert_eq!(longest.routes[0].1, 982);
+    }
+
+    #[test]
+    fn test_top_routes_ties() {
+        let input = "A to B = 1\nA to C = 1\nA to D = 1\nB to C = 1\nB to D = 1\nC to D = 1";
+        let distances = Distances::new(parse_input(input));
+
+        let ranked = distances.top_routes(Objective::Shortest, 5);
+        assert_eq!(ranked.optimum_count, 12);
+        assert_eq!(ranked.routes.len(), 5);
+        assert!(ranked.routes.iter().all(|(_, length)| *length == 3));
+
+        let directed = Distances::directed(parse_input(input));
+        assert_eq!(directed.top_routes(Objective::Longest, 0).optimum_count, 1);
+    }
+
+    // A single path through `n` locations
+    fn chain(n: usize) -> Distances {
+        let name = |i: usize| {
+            let letter = |i: usize| char::from(b'A' + i as u8);
+            format!("{}{}", letter(i / 26), letter(i % 26))
+        };
+        let input = (1..n)
+            .map(|i| format!("{} to {} = 1", name(i - 1), name(i)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Distances::new(parse_input(&input))
+    }
+
+    #[test]
+    fn test_location_limits() {
+        let distances = chain(MAX_HELD_KARP_LOCATIONS);
+        assert_eq!(
+            distances
+                .held_karp(Objective::Shortest)
+                .map(|(_, length)| length),
+            Some(MAX_HELD_KARP_LOCATIONS - 1)
+        );
+        let distances = chain(MAX_SEARCH_LOCATIONS);
+        assert_eq!(distances.all_locations(), usize::MAX >> 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many for Held-Karp")]
+    fn test_held_karp_too_many_locations() {
+        chain(MAX_HELD_KARP_LOCATIONS + 1).held_karp(Objective::Shortest);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many to search")]
+    fn test_search_too_many_locations() {
+        chain(MAX_SEARCH_LOCATIONS + 1).branch_and_bound(Objective::Shortest);
+    }
+
+    mod properties {
+        use proptest::prelude::*;
+        use proptest::sample::Index;
+
+        use super::super::*;
+        use super::{brute_force_route, permutations};
+
+        // A graph over `locations` locations where each pair of them (ordered,
+        // when directed) is connected with a probability picked from `density`
+        fn graph(
+            locations: usize,
+            density: std::ops::RangeInclusive<f64>,
+        ) -> impl Strategy<Value = Distances> {
+            (any::<bool>(), density).prop_flat_map(move |(directed, density)| {
+                let pairs = (0..locations)
+                    .flat_map(|i| (0..locations).map(move |j| (i, j)))
+                    .filter(|&(i, j)| i != j && (directed || i < j))
+                    .collect::<Vec<_>>();
+                let lengths = prop::collection::vec(
+                    (prop::bool::weighted(density), 0..1000usize)
+                        .prop_map(|(connected, distance)| connected.then_some(distance)),
+                    pairs.len(),
+                );
+                lengths.prop_map(move |lengths| {
+                    let distances = pairs
+                        .iter()
+                        .zip(lengths)
+                        .filter_map(|(&(i, j), distance)| {
+                            Some(Distance {
+                                from: format!("L{}", i),
+                                to: format!("L{}", j),
+                                distance: distance?,
+                            })
+                        })
+                        .collect();
+                    if directed {
+                        Distances::directed(distances)
+                    } else {
+                        Distances::new(distances)
+                    }
+                })
+            })
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn held_karp_matches_search(distances in graph(7, 0.3..=1.0)) {
+                let shortest = distances.held_karp(Objective::Shortest);
+                let longest = distances.held_karp(Objective::Longest);
+
+                for (route, length) in shortest.iter().chain(longest.iter()) {
+                    prop_assert_eq!(route.len(), distances.len());
+                    prop_assert_eq!(distances.route_length(route), Some(*length));
+                }
+                prop_assert_eq!(
+                    distances.clone().shortest_route().map(|(_, length)| length),
+                    shortest.map(|(_, length)| length)
+                );
+                prop_assert_eq!(
+                    distances.clone().longest_route().map(|(_, length)| length),
+                    longest.map(|(_, length)| length)
+                );
+            }
+
+            #[test]
+            fn constrained_route_matches_brute_force(
+                distances in graph(6, 0.6..=1.0),
+                closed in any::<bool>(),
+                start in any::<Option<Index>>(),
+                end in any::<Option<Index>>(),
+                precedences in prop::collection::vec(any::<(Index, Index)>(), 0..3),
+            ) {
+                prop_assume!(distances.len() > 0);
+                let id = |index: &Index| index.index(distances.len()) as LocationId;
+                let constraints = RouteConstraints {
+                    closed,
+                    start: start.as_ref().map(id),
+                    end: end.as_ref().map(id),
+                    precedences: precedences.iter().map(|(a, b)| (id(a), id(b))).collect(),
+                };
+
+                for objective in [Objective::Shortest, Objective::Longest] {
+                    let route = distances.constrained_route(objective, &constraints);
+                    if let Some((route, length)) = &route {
+                        prop_assert_eq!(distances.route_length(route), Some(*length));
+                    }
+                    prop_assert_eq!(
+                        route.map(|(_, length)| length),
+                        brute_force_route(&distances, objective, &constraints),
+                        "{:?} {:?}",
+                        objective,
+                        constraints
+                    );
+                }
+            }
+
+            #[test]
+            fn closed_route_with_end_matches_brute_force(distances in graph(6, 1.0..=1.0)) {
+                for end in distances.location_ids() {
+                    let constraints = RouteConstraints {
+                        closed: true,
+                        end: Some(end),
+                        ..Default::default()
+                    };
+                    for objective in [Objective::Shortest, Objective::Longest] {
+                        prop_assert_eq!(
+                            distances
+                                .constrained_route(objective, &constraints)
+                                .map(|(_, length)| length),
+                            brute_force_route(&distances, objective, &constraints),
+                            "{:?} {:?}",
+                            objective,
+                            constraints
+                        );
+                    }
+                }
+            }
+
+            #[test]
+            fn top_routes_matches_brute_force(distances in graph(6, 0.7..=1.0)) {
+                for objective in [Objective::Shortest, Objective::Longest] {
+                    let mut lengths = vec![];
+                    permutations(
+                        &mut vec![],
+                        &mut distances.location_ids().collect(),
+                        &mut |route| {
+                            if distances.directed || route.first() < route.last() {
+                                lengths.extend(distances.route_length(route));
+                            }
+                        },
+                    );
+                    lengths.sort();
+                    if objective == Objective::Longest {
+                        lengths.reverse();
+                    }
+                    let optimum_count = lengths
+                        .iter()
+                        .filter(|&&l| Some(&l) == lengths.first())
+                        .count();
+
+                    let ranked = distances.top_routes(objective, 8);
+                    prop_assert_eq!(ranked.optimum_count, optimum_count);
+                    prop_assert_eq!(
+                        ranked.routes.iter().map(|(_, l)| *l).collect::<Vec<_>>(),
+                        lengths.into_iter().take(8).collect::<Vec<_>>()
+                    );
+                    for (route, length) in &ranked.routes {
+                        prop_assert_eq!(distances.route_length(route), Some(*length));
+                    }
+                }
+            }
+
+            #[test]
+            fn parallel_branch_and_bound(distances in graph(8, 0.5..=1.0)) {
+                for objective in [Objective::Shortest, Objective::Longest] {
+                    let (sequential, sequential_stats) = distances.branch_and_bound(objective);
+                    prop_assert_eq!(
+                        sequential.as_ref().map(|(_, length)| *length),
+                        distances.held_karp(objective).map(|(_, length)| length)
+                    );
+                    prop_assert!(sequential_stats.nodes > 0);
+
+                    for threads in [1, 2, 4] {
+                        let (parallel, stats) =
+                            distances.parallel_branch_and_bound(objective, threads);
+                        prop_assert_eq!(&parallel, &sequential);
+                        prop_assert!(stats.tasks > 0 || sequential.is_none());
+                        prop_assert!(stats.routes >= stats.improvements);
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,29 +1,766 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Write};
+#[cfg(test)]
+use std::iter::Peekable;
+
 use itertools::Itertools;
+use num_bigint::BigUint;
+
+// The puzzle's transform, with decimal counts over decimal digits.
+const DECIMAL: RunLengthTransform<fn(u32) -> u8> = RunLengthTransform {
+    variant: Variant::RunLength,
+    base: 10,
+    digit: |d| d as u8,
+};
+
+// Writes the next sequence into `output`, reusing its allocation.
+fn transform_into(input: &[u8], output: &mut Vec<u8>) {
+    DECIMAL.apply_into(input, output);
+}
+
+#[cfg(test)]
+fn transform_sequence(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() * 2);
+    transform_into(input, &mut output);
+    output
+}
+
+// Applies the transform `iterations` times, swapping between two buffers.
+#[cfg(test)]
+fn look_and_say(sequence: &[u8], iterations: usize) -> Vec<u8> {
+    let mut current = sequence.to_vec();
+    let mut next = Vec::with_capacity(current.len() * 2);
+    for _ in 0..iterations {
+        transform_into(&current, &mut next);
+        std::mem::swap(&mut current, &mut next);
+    }
+    current
+}
+
+// Lazily yields the digits of the next sequence while reading the previous one
+// run by run, so stacking these only holds one run per iteration in memory.
+#[cfg(test)]
+struct LookAndSay<I: Iterator<Item = u8>> {
+    digits: Peekable<I>,
+    // Digits of the current run still to be yielded, in reverse order
+    pending: Vec<u8>,
+}
+
+#[cfg(test)]
+impl<I: Iterator<Item = u8>> LookAndSay<I> {
+    fn new(digits: I) -> Self {
+        LookAndSay {
+            digits: digits.peekable(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl<I: Iterator<Item = u8>> Iterator for LookAndSay<I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if let Some(digit) = self.pending.pop() {
+            return Some(digit);
+        }
+
+        let digit = self.digits.next()?;
+        let mut run = 1;
+        while self.digits.next_if_eq(&digit).is_some() {
+            run += 1;
+        }
+
+        self.pending.push(digit);
+        loop {
+            self.pending.push((run % 10) as u8);
+            run /= 10;
+            if run == 0 {
+                break;
+            }
+        }
+        self.pending.pop()
+    }
+}
+
+#[cfg(test)]
+fn look_and_say_stream<'a>(
+    sequence: &'a [u8],
+    iterations: usize,
+) -> Box<dyn Iterator<Item = u8> + 'a> {
+    let mut digits: Box<dyn Iterator<Item = u8> + 'a> = Box::new(sequence.iter().copied());
+    for _ in 0..iterations {
+        digits = Box::new(LookAndSay::new(digits));
+    }
+    digits
+}
+
+// Conway's 92 common elements: every look-and-say sequence eventually splits
+// into these, and each decays into a fixed list of elements after one step.
+const ELEMENTS: [(&str, &str, &[&str]); 92] = [
+    ("H", "22", &["H"]),
+    (
+        "He",
+        "13112221133211322112211213322112",
+        &["Hf", "Pa", "H", "Ca", "Li"],
+    ),
+    ("Li", "312211322212221121123222112", &["He"]),
+    (
+        "Be",
+        "111312211312113221133211322112211213322112",
+        &["Ge", "Ca", "Li"],
+    ),
+    ("B", "1321132122211322212221121123222112", &["Be"]),
+    ("C", "3113112211322112211213322112", &["B"]),
+    ("N", "111312212221121123222112", &["C"]),
+    ("O", "132112211213322112", &["N"]),
+    ("F", "31121123222112", &["O"]),
+    ("Ne", "111213322112", &["F"]),
+    ("Na", "123222112", &["Ne"]),
+    ("Mg", "3113322112", &["Pm", "Na"]),
+    ("Al", "1113222112", &["Mg"]),
+    ("Si", "1322112", &["Al"]),
+    ("P", "311311222112", &["Ho", "Si"]),
+    ("S", "1113122112", &["P"]),
+    ("Cl", "132112", &["S"]),
+    ("Ar", "3112", &["Cl"]),
+    ("K", "1112", &["Ar"]),
+    ("Ca", "12", &["K"]),
+    ("Sc", "3113112221133112", &["Ho", "Pa", "H", "Ca", "Co"]),
+    ("Ti", "11131221131112", &["Sc"]),
+    ("V", "13211312", &["Ti"]),
+    ("Cr", "31132", &["V"]),
+    ("Mn", "111311222112", &["Cr", "Si"]),
+    ("Fe", "13122112", &["Mn"]),
+    ("Co", "32112", &["Fe"]),
+    ("Ni", "11133112", &["Zn", "Co"]),
+    ("Cu", "131112", &["Ni"]),
+    ("Zn", "312", &["Cu"]),
+    (
+        "Ga",
+        "13221133122211332",
+        &["Eu", "Ca", "Ac", "H", "Ca", "Zn"],
+    ),
+    ("Ge", "31131122211311122113222", &["Ho", "Ga"]),
+    ("As", "11131221131211322113322112", &["Ge", "Na"]),
+    ("Se", "13211321222113222112", &["As"]),
+    ("Br", "3113112211322112", &["Se"]),
+    ("Kr", "11131221222112", &["Br"]),
+    ("Rb", "1321122112", &["Kr"]),
+    ("Sr", "3112112", &["Rb"]),
+    ("Y", "1112133", &["Sr", "U"]),
+    ("Zr", "12322211331222113112211", &["Y", "H", "Ca", "Tc"]),
+    ("Nb", "1113122113322113111221131221", &["Er", "Zr"]),
+    ("Mo", "13211322211312113211", &["Nb"]),
+    ("Tc", "311322113212221", &["Mo"]),
+    ("Ru", "132211331222113112211", &["Eu", "Ca", "Tc"]),
+    ("Rh", "311311222113111221131221", &["Ho", "Ru"]),
+    ("Pd", "111312211312113211", &["Rh"]),
+    ("Ag", "132113212221", &["Pd"]),
+    ("Cd", "3113112211", &["Ag"]),
+    ("In", "11131221", &["Cd"]),
+    ("Sn", "13211", &["In"]),
+    ("Sb", "3112221", &["Pm", "Sn"]),
+    ("Te", "1322113312211", &["Eu", "Ca", "Sb"]),
+    ("I", "311311222113111221", &["Ho", "Te"]),
+    ("Xe", "11131221131211", &["I"]),
+    ("Cs", "13211321", &["Xe"]),
+    ("Ba", "311311", &["Cs"]),
+    ("La", "11131", &["Ba"]),
+    ("Ce", "1321133112", &["La", "H", "Ca", "Co"]),
+    ("Pr", "31131112", &["Ce"]),
+    ("Nd", "111312", &["Pr"]),
+    ("Pm", "132", &["Nd"]),
+    ("Sm", "311332", &["Pm", "Ca", "Zn"]),
+    ("Eu", "1113222", &["Sm"]),
+    ("Gd", "13221133112", &["Eu", "Ca", "Co"]),
+    ("Tb", "3113112221131112", &["Ho", "Gd"]),
+    ("Dy", "111312211312", &["Tb"]),
+    ("Ho", "1321132", &["Dy"]),
+    ("Er", "311311222", &["Ho", "Pm"]),
+    ("Tm", "11131221133112", &["Er", "Ca", "Co"]),
+    ("Yb", "1321131112", &["Tm"]),
+    ("Lu", "311312", &["Yb"]),
+    ("Hf", "11132", &["Lu"]),
+    (
+        "Ta",
+        "13112221133211322112211213322113",
+        &["Hf", "Pa", "H", "Ca", "W"],
+    ),
+    ("W", "312211322212221121123222113", &["Ta"]),
+    (
+        "Re",
+        "111312211312113221133211322112211213322113",
+        &["Ge", "Ca", "W"],
+    ),
+    ("Os", "1321132122211322212221121123222113", &["Re"]),
+    ("Ir", "3113112211322112211213322113", &["Os"]),
+    ("Pt", "111312212221121123222113", &["Ir"]),
+    ("Au", "132112211213322113", &["Pt"]),
+    ("Hg", "31121123222113", &["Au"]),
+    ("Tl", "111213322113", &["Hg"]),
+    ("Pb", "123222113", &["Tl"]),
+    ("Bi", "3113322113", &["Pm", "Pb"]),
+    ("Po", "1113222113", &["Bi"]),
+    ("At", "1322113", &["Po"]),
+    ("Rn", "311311222113", &["Ho", "At"]),
+    ("Fr", "1113122113", &["Rn"]),
+    ("Ra", "132113", &["Fr"]),
+    ("Ac", "3113", &["Ra"]),
+    ("Th", "1113", &["Ac"]),
+    ("Pa", "13", &["Th"]),
+    ("U", "3", &["Pa"]),
+];
+
+fn element_index(name: &str) -> usize {
+    ELEMENTS.iter().position(|(n, _, _)| *n == name).unwrap()
+}
+
+// Conway's transuranic elements, a pair for each digit outside 1..=3. Such a
+// digit stays at the end of its part of the sequence, where Pu decays into Np
+// and Np into common elements and Pu again.
+const PLUTONIUM: &str = "31221132221222112112322211";
+const NEPTUNIUM: &str = "1311222113321132211221121332211";
+
+fn to_digits(sequence: &str) -> Vec<u8> {
+    sequence.bytes().map(|c| c - b'0').collect()
+}
 
-fn transform_sequence(input: &[usize]) -> Vec<usize> {
-    input
+// Digits and decay products of the common elements.
+fn common_elements() -> Vec<(Vec<u8>, Vec<usize>)> {
+    ELEMENTS
         .iter()
-        .group_by(|x| *x)
-        .into_iter()
-        .flat_map(|(k, g)| [g.count(), *k])
+        .map(|(_, digits, decay)| {
+            let decay = decay.iter().map(|name| element_index(name)).collect_vec();
+            (to_digits(digits), decay)
+        })
         .collect()
 }
 
+// Adds the Pu and Np elements ending in `digit` to the table.
+fn push_transuranic(elements: &mut Vec<(Vec<u8>, Vec<usize>)>, digit: u8) {
+    let plutonium = elements.len();
+    let mut neptunium_decay = ["Hf", "Pa", "H", "Ca"].map(element_index).to_vec();
+    neptunium_decay.push(plutonium);
+    let mut digits = to_digits(PLUTONIUM);
+    digits.push(digit);
+    elements.push((digits, vec![plutonium + 1]));
+    let mut digits = to_digits(NEPTUNIUM);
+    digits.push(digit);
+    elements.push((digits, neptunium_decay));
+}
+
+// How many leading digits of a suffix to follow when checking a boundary.
+const PREFIX_DIGITS: usize = 32;
+
+// Whether the sequence still splits between `left` and `suffix` for the next
+// `iterations` steps. The last digit of the left part never changes, so the two
+// sides stay independent as long as the suffix never starts with that digit.
+// Only a prefix of the suffix is followed, dropping the digits describing its
+// last run since that run may continue past the cut.
+fn splits(left: u8, suffix: &[u8], iterations: usize) -> bool {
+    let mut prefix = suffix[..suffix.len().min(PREFIX_DIGITS)].to_vec();
+    let mut complete = prefix.len() == suffix.len();
+    let mut next = Vec::new();
+    for step in 0..=iterations {
+        match prefix.first() {
+            Some(&first) if first != left => {}
+            _ => return false,
+        }
+        if step == iterations {
+            break;
+        }
+        transform_into(&prefix, &mut next);
+        if !complete {
+            next.truncate(next.len() - 2);
+        }
+        if next.len() > PREFIX_DIGITS {
+            next.truncate(PREFIX_DIGITS);
+            complete = false;
+        }
+        std::mem::swap(&mut prefix, &mut next);
+    }
+    true
+}
+
+// Splits `sequence` into `elements`, if it is a concatenation of them whose
+// boundaries survive the next `iterations` steps of the transform.
+fn decompose(
+    sequence: &[u8],
+    elements: &[(Vec<u8>, Vec<usize>)],
+    iterations: usize,
+) -> Option<Vec<usize>> {
+    let mut parsed_until: Vec<Option<usize>> = vec![None; sequence.len() + 1];
+    let mut reachable = vec![false; sequence.len() + 1];
+    reachable[0] = true;
+    for start in 0..sequence.len() {
+        if !reachable[start]
+            || (start > 0 && !splits(sequence[start - 1], &sequence[start..], iterations))
+        {
+            continue;
+        }
+        for (index, (digits, _)) in elements.iter().enumerate() {
+            let end = start + digits.len();
+            if sequence[start..].starts_with(digits) && !reachable[end] {
+                reachable[end] = true;
+                parsed_until[end] = Some(index);
+            }
+        }
+    }
+
+    let mut decomposition = vec![];
+    let mut end = sequence.len();
+    while end > 0 {
+        let index = parsed_until[end]?;
+        decomposition.push(index);
+        end -= elements[index].0.len();
+    }
+    decomposition.reverse();
+    Some(decomposition)
+}
+
+// Length of the sequence after `iterations` steps, tracking how many copies of
+// each element there are instead of the digits themselves. By Conway's
+// cosmological theorem every sequence splits into elements after a few steps,
+// so only those steps are materialized.
+fn look_and_say_length(sequence: &[u8], iterations: usize) -> BigUint {
+    let mut sequence = sequence.to_vec();
+    let mut next = Vec::new();
+    let mut iterations = iterations;
+    let mut elements = common_elements();
+    let mut transuranic = vec![];
+    let decomposition = loop {
+        for digit in sequence.iter().copied() {
+            if !(1..=3).contains(&digit) && !transuranic.contains(&digit) {
+                transuranic.push(digit);
+                push_transuranic(&mut elements, digit);
+            }
+        }
+        if let Some(decomposition) = decompose(&sequence, &elements, iterations) {
+            break decomposition;
+        }
+        if iterations == 0 {
+            return BigUint::from(sequence.len());
+        }
+        transform_into(&sequence, &mut next);
+        std::mem::swap(&mut sequence, &mut next);
+        iterations -= 1;
+    };
+
+    let mut counts = vec![BigUint::from(0u32); elements.len()];
+    for index in decomposition {
+        counts[index] += 1u32;
+    }
+    for _ in 0..iterations {
+        let mut next_counts = vec![BigUint::from(0u32); elements.len()];
+        for (count, (_, decay)) in counts.iter().zip(&elements) {
+            for &product in decay {
+                next_counts[product] += count;
+            }
+        }
+        counts = next_counts;
+    }
+
+    counts
+        .iter()
+        .zip(&elements)
+        .map(|(count, (digits, _))| count * digits.len())
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    // Classic look-and-say: describe each run of equal tokens in order
+    RunLength,
+    // Describe how many times each distinct token appears, in ascending order
+    CountingSort,
+}
+
+// Look-and-say over any token alphabet, writing counts in `base` and turning
+// each count digit into a token with `digit`.
+struct RunLengthTransform<F> {
+    variant: Variant,
+    base: u32,
+    digit: F,
+}
+
+impl<F> RunLengthTransform<F> {
+    // Counts can't be written in bases below 2, so those are rejected.
+    fn new(variant: Variant, base: u32, digit: F) -> Option<Self> {
+        (base >= 2).then_some(RunLengthTransform {
+            variant,
+            base,
+            digit,
+        })
+    }
+}
+
+impl<T: Clone + Ord, F: Fn(u32) -> T> RunLengthTransform<F> {
+    // Appends the digits of `count`, most significant first.
+    fn push_count(&self, output: &mut Vec<T>, count: usize) {
+        let base = self.base as usize;
+        let start = output.len();
+        let mut count = count;
+        loop {
+            output.push((self.digit)((count % base) as u32));
+            count /= base;
+            if count == 0 {
+                break;
+            }
+        }
+        output[start..].reverse();
+    }
+
+    // Writes the next sequence into `output`, reusing its allocation.
+    fn apply_into(&self, input: &[T], output: &mut Vec<T>) {
+        output.clear();
+        match self.variant {
+            Variant::RunLength => {
+                for run in input.chunk_by(|a, b| a == b) {
+                    self.push_count(output, run.len());
+                    output.push(run[0].clone());
+                }
+            }
+            Variant::CountingSort => {
+                for (token, count) in histogram(input) {
+                    self.push_count(output, count);
+                    output.push(token);
+                }
+            }
+        }
+    }
+
+    fn apply(&self, input: &[T]) -> Vec<T> {
+        let mut output = vec![];
+        self.apply_into(input, &mut output);
+        output
+    }
+}
+
+fn histogram<T: Clone + Ord>(sequence: &[T]) -> BTreeMap<T, usize> {
+    let mut histogram = BTreeMap::new();
+    for token in sequence {
+        *histogram.entry(token.clone()).or_default() += 1;
+    }
+    histogram
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct IterationStats<T> {
+    iteration: usize,
+    length: usize,
+    histogram: BTreeMap<T, usize>,
+    // Length relative to the previous iteration, if it was not empty
+    growth: Option<f64>,
+}
+
+fn iteration_stats<T: Clone + Ord, F: Fn(u32) -> T>(
+    transform: &RunLengthTransform<F>,
+    sequence: &[T],
+    iterations: usize,
+) -> Vec<IterationStats<T>> {
+    let mut sequence = sequence.to_vec();
+    let mut stats: Vec<IterationStats<T>> = Vec::with_capacity(iterations + 1);
+    for iteration in 0..=iterations {
+        if iteration > 0 {
+            sequence = transform.apply(&sequence);
+        }
+        let growth = stats
+            .last()
+            .filter(|previous| previous.length > 0)
+            .map(|previous| sequence.len() as f64 / previous.length as f64);
+        stats.push(IterationStats {
+            iteration,
+            length: sequence.len(),
+            histogram: histogram(&sequence),
+            growth,
+        });
+    }
+    stats
+}
+
+// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: impl Display) -> String {
+    let field = field.to_string();
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+// One row per iteration, with a histogram column for every token that shows up
+// in any of them.
+fn stats_csv<T: Ord + Display>(stats: &[IterationStats<T>]) -> String {
+    let tokens = stats
+        .iter()
+        .flat_map(|s| s.histogram.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut csv = String::from("iteration,length,growth");
+    for token in &tokens {
+        write!(csv, ",{}", csv_field(token)).unwrap();
+    }
+    csv.push('\n');
+
+    for s in stats {
+        write!(csv, "{},{},", s.iteration, s.length).unwrap();
+        if let Some(growth) = s.growth {
+            write!(csv, "{:.6}", growth).unwrap();
+        }
+        for token in &tokens {
+            write!(csv, ",{}", s.histogram.get(token).unwrap_or(&0)).unwrap();
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
 fn main() {
     let input = include_str!("../input.txt");
-    let mut sequence = input
-        .trim()
-        .chars()
-        .map(|c| char::to_digit(c, 10).unwrap() as usize)
-        .collect::<Vec<_>>();
 
-    for _ in 0..40 {
-        sequence = transform_sequence(&sequence);
+    // `day10 <run-length|counting-sort> <base> <iterations>` prints per-iteration
+    // statistics as CSV instead of solving the puzzle.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [variant, base, iterations] = args.as_slice() {
+        let variant = match variant.as_str() {
+            "run-length" => Variant::RunLength,
+            "counting-sort" => Variant::CountingSort,
+            _ => panic!("unknown variant {}", variant),
+        };
+        let base = base.parse::<u32>().unwrap();
+        // `char::from_digit` only has digits up to base 36
+        assert!(base <= 36, "base {} is too large", base);
+        let transform =
+            RunLengthTransform::new(variant, base, |d| char::from_digit(d, base).unwrap())
+                .unwrap_or_else(|| panic!("base {} is too small", base));
+        let sequence = input.trim().chars().collect::<Vec<_>>();
+        let stats = iteration_stats(&transform, &sequence, iterations.parse().unwrap());
+        print!("{}", stats_csv(&stats));
+        return;
+    }
+
+    let sequence = to_digits(input.trim());
+
+    println!("{}", look_and_say_length(&sequence, 40));
+    println!("{}", look_and_say_length(&sequence, 50));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits(sequence: &str) -> Vec<u8> {
+        sequence.bytes().map(|c| c - b'0').collect()
+    }
+
+    #[test]
+    fn test_transform_sequence() {
+        assert_eq!(transform_sequence(&digits("1")), digits("11"));
+        assert_eq!(transform_sequence(&digits("1211")), digits("111221"));
+        assert_eq!(transform_sequence(&digits("111221")), digits("312211"));
+        assert_eq!(transform_sequence(&digits("1111111111")), digits("101"));
+        assert_eq!(transform_sequence(&[]), []);
+    }
+
+    #[test]
+    fn test_double_buffer_and_stream() {
+        for start in ["1", "3113322113", "1111111111222"] {
+            let mut sequence = digits(start);
+            for iterations in 0..=20 {
+                assert_eq!(look_and_say(&digits(start), iterations), sequence);
+                assert_eq!(
+                    look_and_say_stream(&digits(start), iterations).collect::<Vec<_>>(),
+                    sequence
+                );
+                sequence = transform_sequence(&sequence);
+            }
+        }
+        assert_eq!(look_and_say_stream(&digits("1"), 40).count(), 82350);
+    }
+
+    #[test]
+    fn test_elements_decay() {
+        for (name, sequence, decay) in ELEMENTS {
+            let decayed = decay
+                .iter()
+                .map(|name| ELEMENTS[element_index(name)].1)
+                .collect::<String>();
+            assert_eq!(
+                transform_sequence(&digits(sequence)),
+                digits(&decayed),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_transuranic_elements_decay() {
+        let mut elements = common_elements();
+        for digit in [0, 4, 5, 6, 7, 8, 9] {
+            push_transuranic(&mut elements, digit);
+        }
+        for (sequence, decay) in &elements[ELEMENTS.len()..] {
+            let decayed = decay
+                .iter()
+                .flat_map(|&index| elements[index].0.iter().copied())
+                .collect_vec();
+            assert_eq!(transform_sequence(sequence), decayed, "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    fn test_look_and_say_length_transuranic() {
+        for start in ["4", "41", "1111111111", "12345"] {
+            let mut sequence = digits(start);
+            for iterations in 0..=45 {
+                assert_eq!(
+                    look_and_say_length(&digits(start), iterations),
+                    BigUint::from(sequence.len()),
+                    "{} after {}",
+                    start,
+                    iterations
+                );
+                sequence = transform_sequence(&sequence);
+            }
+        }
+        assert!(look_and_say_length(&digits("4"), 1000).bits() > 350);
+    }
+
+    #[test]
+    fn test_look_and_say_length() {
+        for start in ["1", "3113322113", "22", "1113222113", "222", "211"] {
+            let mut sequence = digits(start);
+            for iterations in 0..=40 {
+                assert_eq!(
+                    look_and_say_length(&digits(start), iterations),
+                    BigUint::from(sequence.len()),
+                    "{} after {}",
+                    start,
+                    iterations
+                );
+                sequence = transform_sequence(&sequence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_look_and_say_length_brute_force() {
+        for start in 1..=120 {
+            let start = start.to_string();
+            let mut sequence = digits(&start);
+            for iterations in 0..=20 {
+                assert_eq!(
+                    look_and_say_length(&digits(&start), iterations),
+                    BigUint::from(sequence.len()),
+                    "{} after {}",
+                    start,
+                    iterations
+                );
+                sequence = transform_sequence(&sequence);
+            }
+        }
+    }
+
+    #[test]
+    fn test_look_and_say_length_large() {
+        let length = look_and_say_length(&digits("1"), 1000);
+        assert!(length.bits() > 350);
+        assert_eq!(
+            look_and_say_length(&digits("22"), 1000),
+            BigUint::from(2u32)
+        );
+    }
+
+    fn decimal(variant: Variant) -> RunLengthTransform<impl Fn(u32) -> u8> {
+        RunLengthTransform::new(variant, 10, |d| d as u8).unwrap()
+    }
+
+    #[test]
+    fn test_run_length_transform() {
+        let transform = decimal(Variant::RunLength);
+        let mut sequence = digits("3113322113");
+        let mut expected = sequence.clone();
+        for _ in 0..15 {
+            sequence = transform.apply(&sequence);
+            expected = transform_sequence(&expected);
+            assert_eq!(sequence, expected);
+        }
+
+        let words =
+            RunLengthTransform::new(
+                Variant::RunLength,
+                2,
+                |d| {
+                    if d == 0 {
+                        "zero"
+                    } else {
+                        "one"
+                    }
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            words.apply(&["a", "a", "a", "b"]),
+            ["one", "one", "a", "one", "b"]
+        );
+    }
+
+    #[test]
+    fn test_counting_sort_transform() {
+        let transform = decimal(Variant::CountingSort);
+        let sequence = [
+            digits("1"),
+            digits("11"),
+            digits("21"),
+            digits("1112"),
+            digits("3112"),
+        ];
+        for pair in sequence.windows(2) {
+            assert_eq!(transform.apply(&pair[0]), pair[1]);
+        }
+        assert_eq!(transform.apply(&digits("3112")), digits("211213"));
+        // A known fixed point of the counting-sort variant
+        assert_eq!(transform.apply(&digits("21322314")), digits("21322314"));
+    }
+
+    #[test]
+    fn test_stats_csv() {
+        let transform =
+            RunLengthTransform::new(Variant::RunLength, 10, |d| char::from_digit(d, 10).unwrap())
+                .unwrap();
+        let stats = iteration_stats(&transform, &['1'], 3);
+        assert_eq!(
+            stats.iter().map(|s| s.length).collect::<Vec<_>>(),
+            [1, 2, 2, 4]
+        );
+        assert_eq!(stats[1].growth, Some(2.0));
+        assert_eq!(
+            stats_csv(&stats),
+            "iteration,length,growth,1,2\n0,1,,1,0\n1,2,2.000000,2,0\n2,2,1.000000,1,1\n3,4,2.000000,3,1\n"
+        );
+    }
+
+    #[test]
+    fn test_run_length_transform_base() {
+        assert!(RunLengthTransform::new(Variant::RunLength, 0, |d: u32| d).is_none());
+        assert!(RunLengthTransform::new(Variant::RunLength, 1, |d: u32| d).is_none());
+        let binary = RunLengthTransform::new(Variant::RunLength, 2, |d| d).unwrap();
+        assert_eq!(binary.apply(&[7, 7, 7]), [1, 1, 7]);
     }
-    dbg!(sequence.len());
 
-    for _ in 40..50 {
-        sequence = transform_sequence(&sequence);
+    #[test]
+    fn test_stats_csv_quotes_tokens() {
+        let transform = RunLengthTransform::new(Variant::CountingSort, 10, |d| {
+            ["0", "1", "2"][d as usize].to_string()
+        })
+        .unwrap();
+        let sequence = ["a,b", "say \"hi\""].map(String::from);
+        let stats = iteration_stats(&transform, &sequence, 0);
+        assert_eq!(
+            stats_csv(&stats),
+            "iteration,length,growth,\"a,b\",\"say \"\"hi\"\"\"\n0,2,,1,1\n"
+        );
     }
-    dbg!(sequence.len());
 }
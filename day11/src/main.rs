@@ -1,62 +1,544 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use itertools::Itertools;
 
-fn increment_password(password: &mut [u8]) {
-    for c in password.iter_mut().rev() {
-        *c = ((*c - b'a' + 1) % (b'z' - b'a' + 1)) + b'a';
-        if *c != b'a' {
-            break;
-        }
+const FORBIDDEN_LETTERS: [u8; 3] = [b'i', b'o', b'l'];
+
+#[cfg(test)]
+fn increment_password(password: &mut [u8]) -> Result<(), PasswordError> {
+    let position = password
+        .iter()
+        .rposition(|&c| c != b'z')
+        .ok_or(PasswordError::Exhausted)?;
+    password[position] += 1;
+    password[position + 1..].fill(b'a');
+    Ok(())
+}
+
+// Jumps past every password containing a forbidden letter: the first forbidden
+// letter is bumped and everything after it reset, e.g. `ghixyz` -> `ghjaaa`.
+#[cfg(test)]
+fn skip_forbidden_letters(password: &mut [u8]) {
+    if let Some(position) = password.iter().position(|c| FORBIDDEN_LETTERS.contains(c)) {
+        password[position] += 1;
+        password[position + 1..].fill(b'a');
     }
 }
 
+#[cfg(test)]
+fn increment_password_skipping_forbidden(password: &mut [u8]) -> Result<(), PasswordError> {
+    increment_password(password)?;
+    skip_forbidden_letters(password);
+    Ok(())
+}
+
+#[cfg(test)]
 fn contains_three_straight_increasing_letters(password: &[u8]) -> bool {
     password
         .windows(3)
         .any(|w| w[1] as i32 - w[0] as i32 == 1 && w[2] as i32 - w[1] as i32 == 1)
 }
 
+#[cfg(test)]
 fn does_not_contain_forbidden_letters(password: &[u8]) -> bool {
-    !password.iter().any(|c| [b'i', b'o', b'l'].contains(c))
+    !password.iter().any(|c| FORBIDDEN_LETTERS.contains(c))
 }
 
-fn different_non_overlapping_paris(password: &[u8]) -> Vec<u8> {
+// A run of equal letters holds as many pairs as fit in it without overlapping.
+fn non_overlapping_pairs(password: &[u8]) -> usize {
     password
         .iter()
         .group_by(|c| *c)
         .into_iter()
-        .filter_map(|(c, l)| if l.count() == 2 { Some(*c) } else { None })
-        .collect()
+        .map(|(_, l)| l.count() / 2)
+        .sum()
 }
 
-fn contains_two_different_non_overlapping_pairs(password: &[u8]) -> bool {
-    different_non_overlapping_paris(password)
-        .iter()
-        .unique()
-        .count()
-        >= 2
+#[cfg(test)]
+fn contains_two_non_overlapping_pairs(password: &[u8]) -> bool {
+    non_overlapping_pairs(password) >= 2
 }
 
-fn generate_new_password(old_password: &mut [u8]) -> Vec<u8> {
-    increment_password(old_password);
+#[cfg(test)]
+fn is_valid_password(password: &[u8]) -> bool {
+    contains_three_straight_increasing_letters(password)
+        && does_not_contain_forbidden_letters(password)
+        && contains_two_non_overlapping_pairs(password)
+}
+
+#[cfg(test)]
+fn generate_new_password(old_password: &mut [u8]) -> Result<Vec<u8>, PasswordError> {
+    increment_password_skipping_forbidden(old_password)?;
     let tentative_password = old_password;
-    while !contains_three_straight_increasing_letters(tentative_password)
-        || !does_not_contain_forbidden_letters(tentative_password)
-        || !contains_two_different_non_overlapping_pairs(tentative_password)
-    {
-        increment_password(tentative_password);
+    while !is_valid_password(tentative_password) {
+        increment_password_skipping_forbidden(tentative_password)?;
+    }
+
+    Ok(tentative_password.into())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PasswordPolicy {
+    // Letters passwords are made of, in increasing order
+    alphabet: Vec<u8>,
+    forbidden: Vec<u8>,
+    // Length of the increasing straight that must appear
+    straight: usize,
+    // Minimum number of non-overlapping pairs of equal letters
+    pairs: usize,
+    length: Option<usize>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            alphabet: (b'a'..=b'z').collect(),
+            forbidden: FORBIDDEN_LETTERS.to_vec(),
+            straight: 3,
+            pairs: 2,
+            length: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PolicyError {
+    Syntax(String),
+    UnknownKey(String),
+    InvalidValue(String),
+}
+
+// Reads `key = value` lines, with `#` comments, on top of the default policy.
+impl TryFrom<&str> for PasswordPolicy {
+    type Error = PolicyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mut policy = PasswordPolicy::default();
+        for line in value.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| PolicyError::Syntax(line.to_string()))?;
+            let invalid = || PolicyError::InvalidValue(line.to_string());
+            match key {
+                "alphabet" => {
+                    let alphabet = value.bytes().collect::<Vec<_>>();
+                    if alphabet.is_empty()
+                        || alphabet.len() > 64
+                        || alphabet.iter().unique().count() != alphabet.len()
+                    {
+                        return Err(invalid());
+                    }
+                    policy.alphabet = alphabet;
+                }
+                "forbidden" => policy.forbidden = value.bytes().collect(),
+                "straight" => policy.straight = value.parse().map_err(|_| invalid())?,
+                "pairs" => policy.pairs = value.parse().map_err(|_| invalid())?,
+                "length" => {
+                    policy.length = match value {
+                        "any" => None,
+                        _ => Some(value.parse().map_err(|_| invalid())?),
+                    }
+                }
+                _ => return Err(PolicyError::UnknownKey(key.to_string())),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Violation {
+    Length { expected: usize, actual: usize },
+    OutsideAlphabet(u8),
+    Forbidden(u8),
+    NoStraight { length: usize },
+    NotEnoughPairs { required: usize, found: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Length { expected, actual } => {
+                write!(f, "has {} letters instead of {}", actual, expected)
+            }
+            Violation::OutsideAlphabet(c) => {
+                write!(f, "contains '{}', which is not in the alphabet", *c as char)
+            }
+            Violation::Forbidden(c) => write!(f, "contains forbidden letter '{}'", *c as char),
+            Violation::NoStraight { length } => {
+                write!(f, "has no straight of {} increasing letters", length)
+            }
+            Violation::NotEnoughPairs { required, found } => {
+                write!(f, "has {} pairs instead of {}", found, required)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PasswordError {
+    // There is no valid password after the given one
+    Exhausted,
+    // The given password can't be used as a starting point
+    Invalid(Violation),
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PasswordError::Exhausted => write!(f, "no more passwords"),
+            PasswordError::Invalid(violation) => write!(f, "invalid password: {}", violation),
+        }
+    }
+}
+
+// What the rules need to know about a password prefix, read left to right.
+// Letters are indices into the policy alphabet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+struct RulesState {
+    last: Option<u8>,
+    // Length of the increasing straight ending at `last`, saturating at the
+    // policy straight length
+    straight: usize,
+    has_straight: bool,
+    // Pairs seen so far, saturating at the policy pair count
+    pairs: usize,
+    // Whether `last` isn't part of a pair yet, so the next letter can pair with it
+    unpaired: bool,
+}
+
+impl RulesState {
+    fn new(policy: &PasswordPolicy) -> Self {
+        RulesState {
+            has_straight: policy.straight == 0,
+            ..Default::default()
+        }
+    }
+
+    // Letters pair up greedily from the start of each run, which finds as many
+    // non-overlapping pairs as there are.
+    fn push(self, c: u8, policy: &PasswordPolicy) -> RulesState {
+        let mut next = self;
+        if self.last == Some(c) && self.unpaired {
+            next.pairs = (self.pairs + 1).min(policy.pairs);
+            next.unpaired = false;
+        } else {
+            next.unpaired = next.pairs < policy.pairs;
+        }
+        next.straight = if self.last.is_some_and(|last| last + 1 == c) {
+            (self.straight + 1).min(policy.straight)
+        } else {
+            1
+        };
+        next.has_straight |= next.straight >= policy.straight;
+        next.last = Some(c);
+        next
+    }
+
+    fn is_valid(self, policy: &PasswordPolicy) -> bool {
+        self.has_straight && self.pairs >= policy.pairs
+    }
+}
+
+impl PasswordPolicy {
+    fn index(&self, c: u8) -> Option<u8> {
+        self.alphabet.iter().position(|&a| a == c).map(|i| i as u8)
+    }
+
+    fn allowed(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.alphabet.len() as u8)
+            .filter(|&i| !self.forbidden.contains(&self.alphabet[i as usize]))
+    }
+
+    fn violations(&self, password: &[u8]) -> Vec<Violation> {
+        let mut violations = vec![];
+        if let Some(expected) = self.length.filter(|&l| l != password.len()) {
+            violations.push(Violation::Length {
+                expected,
+                actual: password.len(),
+            });
+        }
+        for &c in password.iter().unique() {
+            if self.index(c).is_none() {
+                violations.push(Violation::OutsideAlphabet(c));
+            } else if self.forbidden.contains(&c) {
+                violations.push(Violation::Forbidden(c));
+            }
+        }
+
+        let indices = password.iter().map(|&c| self.index(c)).collect::<Vec<_>>();
+        let mut straight = 1;
+        let has_straight = self.straight <= 1 && !(self.straight == 1 && password.is_empty())
+            || indices.windows(2).any(|w| {
+                straight = match (w[0], w[1]) {
+                    (Some(a), Some(b)) if a + 1 == b => straight + 1,
+                    _ => 1,
+                };
+                straight >= self.straight
+            });
+        if !has_straight {
+            violations.push(Violation::NoStraight {
+                length: self.straight,
+            });
+        }
+
+        let found = non_overlapping_pairs(password);
+        if found < self.pairs {
+            violations.push(Violation::NotEnoughPairs {
+                required: self.pairs,
+                found,
+            });
+        }
+
+        violations
+    }
+
+    fn can_complete(
+        &self,
+        state: RulesState,
+        remaining: usize,
+        memo: &mut HashMap<(RulesState, usize), bool>,
+    ) -> bool {
+        if remaining == 0 {
+            return state.is_valid(self);
+        }
+        if let Some(&result) = memo.get(&(state, remaining)) {
+            return result;
+        }
+        let result = self
+            .allowed()
+            .any(|c| self.can_complete(state.push(c, self), remaining - 1, memo));
+        memo.insert((state, remaining), result);
+        result
+    }
+
+    // Number of ways to complete a prefix in `state` with `remaining` letters
+    // into a valid password.
+    fn completions(
+        &self,
+        state: RulesState,
+        remaining: usize,
+        memo: &mut HashMap<(RulesState, usize), u128>,
+    ) -> u128 {
+        if remaining == 0 {
+            return state.is_valid(self) as u128;
+        }
+        if let Some(&count) = memo.get(&(state, remaining)) {
+            return count;
+        }
+        let count = self
+            .allowed()
+            .map(|c| self.completions(state.push(c, self), remaining - 1, memo))
+            .sum();
+        memo.insert((state, remaining), count);
+        count
+    }
+
+    // Number of valid passwords of the same length smaller than `password`
+    fn count_below(&self, password: &[u8]) -> Result<u128, PasswordError> {
+        let password = self.indices(password)?;
+        let n = password.len();
+        let mut memo = HashMap::new();
+        let mut count = 0;
+        let mut state = RulesState::new(self);
+        for (i, &letter) in password.iter().enumerate() {
+            for c in self.allowed().take_while(|&c| c < letter) {
+                count += self.completions(state.push(c, self), n - i - 1, &mut memo);
+            }
+            if self.forbidden.contains(&self.alphabet[letter as usize]) {
+                break;
+            }
+            state = state.push(letter, self);
+        }
+        Ok(count)
+    }
+
+    // Number of valid passwords between `from` and `to`, both included
+    fn count_between(&self, from: &[u8], to: &[u8]) -> Result<u128, PasswordError> {
+        if from.len() != to.len() {
+            return Err(PasswordError::Invalid(Violation::Length {
+                expected: from.len(),
+                actual: to.len(),
+            }));
+        }
+        let below_to = self.count_below(to)? + self.violations(to).is_empty() as u128;
+        Ok(below_to.saturating_sub(self.count_below(from)?))
+    }
+
+    // Position of a valid password among the valid passwords of its length
+    #[cfg(test)]
+    fn rank(&self, password: &[u8]) -> Result<u128, PasswordError> {
+        if let Some(violation) = self.violations(password).into_iter().next() {
+            return Err(PasswordError::Invalid(violation));
+        }
+        self.count_below(password)
     }
 
-    tentative_password.into()
+    // The valid password of the given length at position `rank`
+    #[cfg(test)]
+    fn unrank(&self, length: usize, mut rank: u128) -> Result<Vec<u8>, PasswordError> {
+        if let Some(expected) = self.length.filter(|&l| l != length) {
+            return Err(PasswordError::Invalid(Violation::Length {
+                expected,
+                actual: length,
+            }));
+        }
+        let mut memo = HashMap::new();
+        let mut password = Vec::with_capacity(length);
+        let mut state = RulesState::new(self);
+        while password.len() < length {
+            let remaining = length - password.len() - 1;
+            let c = self
+                .allowed()
+                .find(|&c| {
+                    let count = self.completions(state.push(c, self), remaining, &mut memo);
+                    if rank < count {
+                        return true;
+                    }
+                    rank -= count;
+                    false
+                })
+                .ok_or(PasswordError::Exhausted)?;
+            password.push(self.alphabet[c as usize]);
+            state = state.push(c, self);
+        }
+        Ok(password)
+    }
+
+    // Builds the smallest valid password greater than `old_password` directly:
+    // keep the longest possible prefix, bump the next letter, and fill the rest
+    // with the smallest letters that still leave the rules satisfiable.
+    fn next_password(&self, old_password: &[u8]) -> Result<Vec<u8>, PasswordError> {
+        let old = self.indices(old_password)?;
+        let n = old.len();
+        let allowed_prefix = old
+            .iter()
+            .position(|&c| self.forbidden.contains(&self.alphabet[c as usize]))
+            .unwrap_or(n);
+
+        let mut prefix_states = vec![RulesState::new(self)];
+        for &c in &old[..allowed_prefix] {
+            prefix_states.push(prefix_states.last().unwrap().push(c, self));
+        }
+
+        let mut memo = HashMap::new();
+        for keep in (0..n.min(allowed_prefix + 1)).rev() {
+            let state = prefix_states[keep];
+            let Some(c) = self.allowed().find(|&c| {
+                c > old[keep] && self.can_complete(state.push(c, self), n - keep - 1, &mut memo)
+            }) else {
+                continue;
+            };
+
+            let mut password = old[..keep].to_vec();
+            password.push(c);
+            let mut state = state.push(c, self);
+            while password.len() < n {
+                let remaining = n - password.len() - 1;
+                let c = self
+                    .allowed()
+                    .find(|&c| self.can_complete(state.push(c, self), remaining, &mut memo))
+                    .unwrap();
+                password.push(c);
+                state = state.push(c, self);
+            }
+
+            let password = password
+                .into_iter()
+                .map(|c| self.alphabet[c as usize])
+                .collect::<Vec<_>>();
+            debug_assert!(self.violations(&password).is_empty());
+            return Ok(password);
+        }
+
+        Err(PasswordError::Exhausted)
+    }
+
+    // Alphabet indices of a password that can be used as a starting point
+    fn indices(&self, password: &[u8]) -> Result<Vec<u8>, PasswordError> {
+        if let Some(expected) = self.length.filter(|&l| l != password.len()) {
+            return Err(PasswordError::Invalid(Violation::Length {
+                expected,
+                actual: password.len(),
+            }));
+        }
+        password
+            .iter()
+            .map(|&c| {
+                self.index(c)
+                    .ok_or(PasswordError::Invalid(Violation::OutsideAlphabet(c)))
+            })
+            .collect()
+    }
+
+    // Successive valid passwords after `start`
+    fn valid_passwords(&self, start: &[u8]) -> Result<ValidPasswords<'_>, PasswordError> {
+        self.indices(start)?;
+        Ok(ValidPasswords {
+            policy: self,
+            current: Some(start.to_vec()),
+        })
+    }
+}
+
+struct ValidPasswords<'a> {
+    policy: &'a PasswordPolicy,
+    // `None` once the password space is exhausted
+    current: Option<Vec<u8>>,
+}
+
+impl Iterator for ValidPasswords<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.policy.next_password(self.current.as_ref()?).ok();
+        self.current.clone_from(&next);
+        next
+    }
+}
+
+#[cfg(test)]
+fn next_valid_password(old_password: &[u8]) -> Result<Vec<u8>, PasswordError> {
+    let password = PasswordPolicy::default().next_password(old_password)?;
+    debug_assert!(is_valid_password(&password));
+    Ok(password)
 }
 
 fn main() {
     let input = include_str!("../input.txt");
-    let mut password = input.trim().as_bytes().to_owned();
-    let mut new_password = generate_new_password(&mut password);
-    println!("{}", String::from_utf8_lossy(&new_password));
+    let password = input.trim().as_bytes().to_owned();
+
+    // `day11 [--policy <path>] [<from> <to>]`: a policy file replaces the puzzle
+    // rules, and two passwords count the valid ones between them.
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (policy, args) = match args.as_slice() {
+        [flag, path, rest @ ..] if flag == "--policy" => {
+            let config = std::fs::read_to_string(path).unwrap();
+            (PasswordPolicy::try_from(config.as_str()).unwrap(), rest)
+        }
+        rest => (PasswordPolicy::default(), rest),
+    };
 
-    let new_password = generate_new_password(&mut new_password);
-    println!("{}", String::from_utf8_lossy(&new_password));
+    let passwords = policy.valid_passwords(&password).unwrap();
+    for new_password in passwords.take(2) {
+        println!("{}", String::from_utf8_lossy(&new_password));
+    }
+
+    match args {
+        [] => {}
+        [from, to] => {
+            let count = policy
+                .count_between(from.as_bytes(), to.as_bytes())
+                .unwrap();
+            println!("{}", count);
+        }
+        _ => panic!("usage: day11 [--policy <path>] [<from> <to>]"),
+    }
 }
 
 #[cfg(test)]
@@ -68,12 +550,19 @@ mod tests {
     #[test]
     fn test_increment_password() {
         let mut password = b"abc".to_vec();
-        increment_password(&mut password);
+        increment_password(&mut password).unwrap();
         assert_eq!(password, b"abd");
 
         let mut password = b"xz".to_vec();
-        increment_password(&mut password);
+        increment_password(&mut password).unwrap();
         assert_eq!(password, b"ya");
+
+        let mut password = b"zzzz".to_vec();
+        assert_eq!(
+            increment_password(&mut password),
+            Err(PasswordError::Exhausted)
+        );
+        assert_eq!(password, b"zzzz");
     }
 
     #[test]
@@ -88,11 +577,324 @@ mod tests {
         ];
 
         for (p, n) in passwords.iter().zip(next_passwords) {
-            let actual = generate_new_password(p.clone().deref_mut());
+            let actual = generate_new_password(p.clone().deref_mut()).unwrap();
             assert_eq!(
                 String::from_utf8_lossy(&actual),
                 String::from_utf8_lossy(&n),
             );
         }
     }
+
+    #[test]
+    fn test_skip_forbidden_letters() {
+        let mut password = b"ghijklmn".to_vec();
+        increment_password_skipping_forbidden(&mut password).unwrap();
+        assert_eq!(password, b"ghjaaaaa");
+
+        let mut password = b"abcdefgh".to_vec();
+        increment_password_skipping_forbidden(&mut password).unwrap();
+        assert_eq!(password, b"abcdefgj");
+
+        let mut password = b"aknzz".to_vec();
+        increment_password_skipping_forbidden(&mut password).unwrap();
+        assert_eq!(password, b"akpaa");
+
+        for start in [b"abchzz".to_vec(), b"xkzzzz".to_vec(), b"aaanzz".to_vec()] {
+            let mut skipping = start.clone();
+            increment_password_skipping_forbidden(&mut skipping).unwrap();
+            let mut plain = start;
+            increment_password(&mut plain).unwrap();
+            while !does_not_contain_forbidden_letters(&plain) {
+                increment_password(&mut plain).unwrap();
+            }
+            assert_eq!(skipping, plain);
+        }
+    }
+
+    #[test]
+    fn test_next_valid_password() {
+        for (p, n) in [("abcdefgh", "abcdffaa"), ("ghijklmn", "ghjaabcc")] {
+            let actual = next_valid_password(p.as_bytes()).unwrap();
+            assert_eq!(String::from_utf8_lossy(&actual), n);
+        }
+        assert_eq!(
+            next_valid_password(b"zzzzzzzz"),
+            Err(PasswordError::Exhausted)
+        );
+        assert_eq!(next_valid_password(b"abcd"), Err(PasswordError::Exhausted));
+        assert_eq!(
+            next_valid_password(b"abc-"),
+            Err(PasswordError::Invalid(Violation::OutsideAlphabet(b'-')))
+        );
+    }
+
+    #[test]
+    fn test_pairs() {
+        assert_eq!(non_overlapping_pairs(b"aaaa"), 2);
+        assert_eq!(non_overlapping_pairs(b"aaab"), 1);
+        assert_eq!(non_overlapping_pairs(b"aaa"), 1);
+        assert_eq!(non_overlapping_pairs(b"aabccc"), 2);
+        assert_eq!(non_overlapping_pairs(b"abab"), 0);
+
+        assert!(contains_two_non_overlapping_pairs(b"aaaa"));
+        assert!(!contains_two_non_overlapping_pairs(b"aaab"));
+        assert!(contains_two_non_overlapping_pairs(b"aaabbb"));
+        assert!(is_valid_password(b"abcdddee"));
+        assert_eq!(next_valid_password(b"abcdddd"), Ok(b"abcddee".to_vec()));
+    }
+
+    #[test]
+    fn test_valid_passwords() {
+        let policy = PasswordPolicy::default();
+        let passwords = policy
+            .valid_passwords(b"abcdefgh")
+            .unwrap()
+            .take(3)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            passwords,
+            vec![
+                b"abcdffaa".to_vec(),
+                b"abcdffbb".to_vec(),
+                b"abcdffcc".to_vec()
+            ]
+        );
+
+        let policy = PasswordPolicy::try_from("alphabet = abc\nstraight = 3\npairs = 1").unwrap();
+        let passwords = policy.valid_passwords(b"aaaa").unwrap().collect::<Vec<_>>();
+        assert!(passwords.iter().all(|p| policy.violations(p).is_empty()));
+        assert_eq!(passwords.first(), Some(&b"aabc".to_vec()));
+        assert_eq!(passwords.last(), Some(&b"abcc".to_vec()));
+
+        assert!(matches!(
+            policy.valid_passwords(b"abcd"),
+            Err(PasswordError::Invalid(Violation::OutsideAlphabet(b'd')))
+        ));
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let config = "# stricter rules\nalphabet = fedcba\nforbidden = c\n\nstraight = 2\npairs = 1\nlength = 5\n";
+        let policy = PasswordPolicy::try_from(config).unwrap();
+        assert_eq!(
+            policy,
+            PasswordPolicy {
+                alphabet: b"fedcba".to_vec(),
+                forbidden: b"c".to_vec(),
+                straight: 2,
+                pairs: 1,
+                length: Some(5),
+            }
+        );
+        assert_eq!(PasswordPolicy::try_from(""), Ok(PasswordPolicy::default()));
+
+        assert_eq!(
+            PasswordPolicy::try_from("straight 3"),
+            Err(PolicyError::Syntax("straight 3".to_string()))
+        );
+        assert_eq!(
+            PasswordPolicy::try_from("triples = 1"),
+            Err(PolicyError::UnknownKey("triples".to_string()))
+        );
+        assert_eq!(
+            PasswordPolicy::try_from("alphabet = abca"),
+            Err(PolicyError::InvalidValue("alphabet = abca".to_string()))
+        );
+        assert_eq!(
+            PasswordPolicy::try_from("pairs = two"),
+            Err(PolicyError::InvalidValue("pairs = two".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_violations() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(policy.violations(b"abcdffaa"), vec![]);
+        assert_eq!(
+            policy.violations(b"hijklmmn"),
+            vec![
+                Violation::Forbidden(b'i'),
+                Violation::Forbidden(b'l'),
+                Violation::NotEnoughPairs {
+                    required: 2,
+                    found: 1
+                },
+            ]
+        );
+        assert_eq!(
+            policy.violations(b"abbceffg"),
+            vec![Violation::NoStraight { length: 3 }]
+        );
+
+        let policy = PasswordPolicy {
+            length: Some(8),
+            ..Default::default()
+        };
+        let violations = policy.violations(b"aBc");
+        assert_eq!(
+            violations,
+            vec![
+                Violation::Length {
+                    expected: 8,
+                    actual: 3
+                },
+                Violation::OutsideAlphabet(b'B'),
+                Violation::NoStraight { length: 3 },
+                Violation::NotEnoughPairs {
+                    required: 2,
+                    found: 0
+                },
+            ]
+        );
+        assert_eq!(violations[0].to_string(), "has 3 letters instead of 8");
+        assert_eq!(
+            violations[1].to_string(),
+            "contains 'B', which is not in the alphabet"
+        );
+    }
+
+    #[test]
+    fn test_policy_next_password_matches_brute_force() {
+        let policy = PasswordPolicy::try_from(
+            "alphabet = fedcba\nforbidden = c\nstraight = 3\npairs = 1\nlength = 5",
+        )
+        .unwrap();
+        let alphabet = &policy.alphabet;
+        let all = (0..5)
+            .map(|_| alphabet.iter().copied())
+            .multi_cartesian_product()
+            .collect::<Vec<_>>();
+        let is_valid = all
+            .iter()
+            .map(|p| policy.violations(p).is_empty())
+            .collect::<Vec<_>>();
+        assert!(is_valid.contains(&true));
+
+        for (i, password) in all.iter().enumerate() {
+            let expected = (i + 1..all.len())
+                .find(|&j| is_valid[j])
+                .map(|j| all[j].clone())
+                .ok_or(PasswordError::Exhausted);
+            assert_eq!(
+                policy.next_password(password),
+                expected,
+                "{}",
+                String::from_utf8_lossy(password)
+            );
+        }
+        assert_eq!(
+            policy.next_password(b"fedc"),
+            Err(PasswordError::Invalid(Violation::Length {
+                expected: 5,
+                actual: 4
+            }))
+        );
+        assert_eq!(
+            policy.next_password(b"fedcz"),
+            Err(PasswordError::Invalid(Violation::OutsideAlphabet(b'z')))
+        );
+    }
+
+    #[test]
+    fn test_count_between() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(policy.count_between(b"abcdffaa", b"abcdffaa"), Ok(1));
+        assert_eq!(policy.count_between(b"abcdefgh", b"abcdffcc"), Ok(3));
+        assert_eq!(policy.count_between(b"abcdffcc", b"abcdefgh"), Ok(0));
+        assert_eq!(
+            policy.count_between(b"abcdefgh", b"zzzzzzz"),
+            Err(PasswordError::Invalid(Violation::Length {
+                expected: 8,
+                actual: 7
+            }))
+        );
+
+        let total = policy.count_between(b"aaaaaaaa", b"zzzzzzzz").unwrap();
+        let below = policy.count_below(b"abcdefgh").unwrap();
+        assert_eq!(
+            policy.count_between(b"abcdefgh", b"zzzzzzzz"),
+            Ok(total - below)
+        );
+
+        assert_eq!(policy.unrank(8, total), Err(PasswordError::Exhausted));
+    }
+
+    #[test]
+    fn test_rank_matches_brute_force() {
+        let policy = PasswordPolicy::try_from(
+            "alphabet = fedcba\nforbidden = c\nstraight = 3\npairs = 1\nlength = 5",
+        )
+        .unwrap();
+        let all = (0..5)
+            .map(|_| policy.alphabet.iter().copied())
+            .multi_cartesian_product()
+            .collect::<Vec<_>>();
+        let valid = all
+            .iter()
+            .filter(|p| policy.violations(p).is_empty())
+            .collect::<Vec<_>>();
+
+        for (rank, &password) in valid.iter().enumerate() {
+            assert_eq!(policy.rank(password), Ok(rank as u128));
+            assert_eq!(policy.unrank(5, rank as u128).as_ref(), Ok(password));
+        }
+        assert_eq!(
+            policy.unrank(5, valid.len() as u128),
+            Err(PasswordError::Exhausted)
+        );
+        assert_eq!(
+            policy.rank(b"fffff"),
+            Err(PasswordError::Invalid(Violation::NoStraight { length: 3 }))
+        );
+
+        // `all` is in alphabet order, which is not byte order here
+        let valid_before = all
+            .iter()
+            .scan(0, |count, p| {
+                *count += policy.violations(p).is_empty() as u128;
+                Some(*count)
+            })
+            .collect::<Vec<_>>();
+        for i in (0..all.len()).step_by(197) {
+            for j in (i..all.len()).step_by(181) {
+                let expected = valid_before[j] - valid_before[i]
+                    + policy.violations(&all[i]).is_empty() as u128;
+                assert_eq!(policy.count_between(&all[i], &all[j]), Ok(expected));
+            }
+        }
+    }
+
+    mod properties {
+        use proptest::prelude::*;
+
+        use super::super::*;
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            // Passwords start at most at `w`, keeping clear of the end of the
+            // password space, which the brute force search takes long to get through.
+            #[test]
+            fn next_valid_password_matches_brute_force(password in "[a-w][a-z]{5}") {
+                let password = password.into_bytes();
+                let expected = generate_new_password(&mut password.clone());
+                prop_assert_eq!(next_valid_password(&password), expected);
+            }
+
+            // Walking the valid passwords one by one from any point goes up one rank
+            // at a time.
+            #[test]
+            fn valid_passwords_go_up_one_rank_at_a_time(start in "[a-z]{8}") {
+                let policy = PasswordPolicy::default();
+                let start = start.into_bytes();
+                let below = policy.count_below(&start).unwrap();
+                let skipped = policy.violations(&start).is_empty() as u128;
+                for (i, password) in policy.valid_passwords(&start).unwrap().take(3).enumerate() {
+                    let rank = policy.rank(&password).unwrap();
+                    prop_assert_eq!(rank, below + i as u128 + skipped);
+                    prop_assert_eq!(policy.unrank(8, rank), Ok(password));
+                }
+            }
+        }
+    }
 }
@@ -1,6 +1,17 @@
-use serde_json::Value;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::ops::Add;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
-fn iterate_values<'a>(root: &'a Value) -> Box<dyn Iterator<Item = &Value> + 'a> {
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::{Number, Value};
+
+#[cfg(test)]
+fn iterate_values<'a>(root: &'a Value) -> Box<dyn Iterator<Item = &'a Value> + 'a> {
     match root {
         Value::Array(a) => Box::new(a.iter().flat_map(iterate_values)),
         Value::Object(map) => Box::new(map.values().flat_map(iterate_values)),
@@ -8,7 +19,7 @@ fn iterate_values<'a>(root: &'a Value) -> Box<dyn Iterator<Item = &Value> + 'a>
     }
 }
 
-fn iterate_values_skip_reds<'a>(root: &'a Value) -> Box<dyn Iterator<Item = &Value> + 'a> {
+fn iterate_values_skip_reds<'a>(root: &'a Value) -> Box<dyn Iterator<Item = &'a Value> + 'a> {
     match root {
         Value::Array(a) => Box::new(a.iter().flat_map(iterate_values_skip_reds)),
         Value::Object(map) => {
@@ -34,15 +45,1053 @@ fn extract_numbers<'a>(
         .flatten()
 }
 
+// Finds the `-?[0-9]+` tokens in a document without parsing it, skipping over
+// string literals. Fractions and exponents are read as separate tokens, and
+// tokens too large for an i64 are dropped.
+fn scan_numbers(input: &[u8]) -> impl Iterator<Item = i64> + '_ {
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        while i < input.len() {
+            match input[i] {
+                b'"' => {
+                    i += 1;
+                    while i < input.len() && input[i] != b'"' {
+                        // Skips whatever is escaped, quotes included
+                        i += if input[i] == b'\\' { 2 } else { 1 };
+                    }
+                    i += 1;
+                }
+                b'-' | b'0'..=b'9' => {
+                    let start = i;
+                    i += 1;
+                    while i < input.len() && input[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    // A lone `-` is not a number
+                    let token = std::str::from_utf8(&input[start..i]).unwrap();
+                    if let Ok(n) = token.parse() {
+                        return Some(n);
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        None
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathToken {
+    Key(String),
+    Index(usize),
+}
+
+// Formats a path as a JSON pointer (RFC 6901)
+fn json_pointer(path: &[PathToken]) -> String {
+    path.iter()
+        .map(|token| match token {
+            PathToken::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+            PathToken::Index(i) => format!("/{}", i),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    // `*` or `[*]`, any single key or index
+    Wildcard,
+    // `..`, any number of keys or indices
+    Descendants,
+}
+
+impl Segment {
+    fn matches(&self, token: &PathToken) -> bool {
+        match (self, token) {
+            (Segment::Key(k), PathToken::Key(key)) => k == key,
+            (Segment::Index(i), PathToken::Index(index)) => i == index,
+            (Segment::Wildcard, _) => true,
+            _ => false,
+        }
+    }
+}
+
+// A JSONPath-like selector such as `$.a[0]`, `$..b` or `$.c[*].d`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selector(Vec<Segment>);
+
+impl Selector {
+    fn matches(&self, path: &[PathToken]) -> bool {
+        fn matches_from(segments: &[Segment], path: &[PathToken]) -> bool {
+            match segments.split_first() {
+                None => path.is_empty(),
+                Some((Segment::Descendants, rest)) => {
+                    (0..=path.len()).any(|skip| matches_from(rest, &path[skip..]))
+                }
+                Some((segment, rest)) => path.split_first().is_some_and(|(token, path)| {
+                    segment.matches(token) && matches_from(rest, path)
+                }),
+            }
+        }
+        matches_from(&self.0, path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InvalidRule(String);
+
+impl FromStr for Selector {
+    type Err = InvalidRule;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidRule(s.to_string());
+        let mut rest = s.strip_prefix('$').ok_or_else(invalid)?;
+        let mut segments = vec![];
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix("..") {
+                segments.push(Segment::Descendants);
+                rest = r;
+                // Either `$..a` or `$..[0]`
+                if !r.starts_with('[') {
+                    let end = r.find(['.', '[']).unwrap_or(r.len());
+                    segments.push(name_segment(&r[..end]).ok_or_else(invalid)?);
+                    rest = &r[end..];
+                }
+            } else if let Some(r) = rest.strip_prefix('.') {
+                let end = r.find(['.', '[']).unwrap_or(r.len());
+                segments.push(name_segment(&r[..end]).ok_or_else(invalid)?);
+                rest = &r[end..];
+            } else if let Some(r) = rest.strip_prefix('[') {
+                let (inside, r) = r.split_once(']').ok_or_else(invalid)?;
+                let segment = if inside == "*" {
+                    Segment::Wildcard
+                } else if let Some(key) =
+                    inside.strip_prefix('\'').and_then(|k| k.strip_suffix('\''))
+                {
+                    Segment::Key(key.to_string())
+                } else {
+                    Segment::Index(inside.parse().map_err(|_| invalid())?)
+                };
+                segments.push(segment);
+                rest = r;
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(Selector(segments))
+    }
+}
+
+fn name_segment(name: &str) -> Option<Segment> {
+    match name {
+        "" => None,
+        "*" => Some(Segment::Wildcard),
+        _ => Some(Segment::Key(name.to_string())),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Array,
+    Object,
+    Any,
+}
+
+impl Container {
+    fn matches(self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (Container::Array | Container::Any, Value::Array(_))
+                | (Container::Object | Container::Any, Value::Object(_))
+        )
+    }
+}
+
+// A condition under which an array or object is left out of the sum, along
+// with everything inside it
+#[derive(Debug, Clone, PartialEq)]
+enum Prune {
+    // One of its elements or values equals the given one
+    HasValue(Container, Value),
+    HasKey(String),
+    // Nested deeper than the given depth, the root being at depth 0
+    DeeperThan(usize),
+    Selected(Selector),
+}
+
+impl Prune {
+    fn matches(&self, value: &Value, path: &[PathToken]) -> bool {
+        if !Container::Any.matches(value) {
+            return false;
+        }
+        match self {
+            Prune::HasValue(container, v) => {
+                container.matches(value)
+                    && match value {
+                        Value::Array(a) => a.contains(v),
+                        Value::Object(map) => map.values().any(|value| value == v),
+                        _ => false,
+                    }
+            }
+            Prune::HasKey(key) => value.get(key).is_some(),
+            Prune::DeeperThan(depth) => path.len() > *depth,
+            Prune::Selected(selector) => selector.matches(path),
+        }
+    }
+}
+
+// Rules are written `value:<json>`, `array-value:<json>`, `object-value:<json>`,
+// `key:<name>`, `depth:<n>` or `select:<selector>`.
+impl FromStr for Prune {
+    type Err = InvalidRule;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidRule(s.to_string());
+        let (kind, argument) = s.split_once(':').ok_or_else(invalid)?;
+        let value = || serde_json::from_str(argument).map_err(|_| invalid());
+        match kind {
+            "value" => Ok(Prune::HasValue(Container::Any, value()?)),
+            "array-value" => Ok(Prune::HasValue(Container::Array, value()?)),
+            "object-value" => Ok(Prune::HasValue(Container::Object, value()?)),
+            "key" => Ok(Prune::HasKey(argument.to_string())),
+            "depth" => Ok(Prune::DeeperThan(argument.parse().map_err(|_| invalid())?)),
+            "select" => Ok(Prune::Selected(argument.parse()?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// Every number in a document, by JSON pointer, split by whether it was pruned.
+// Numbers that aren't an i64 are never summed, pruned or not.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Audit {
+    counted: Vec<(String, i64)>,
+    skipped: Vec<(String, i64)>,
+    ignored: Vec<(String, Number)>,
+}
+
+impl Audit {
+    // Summed as i128, since even two i64s can overflow
+    fn sum(&self) -> i128 {
+        self.counted.iter().map(|(_, n)| i128::from(*n)).sum()
+    }
+}
+
+// Calls `f` with the path of every number in the document and whether a rule
+// pruned it
+fn walk(root: &Value, rules: &[Prune], mut f: impl FnMut(&[PathToken], &Number, bool)) {
+    fn visit(
+        value: &Value,
+        path: &mut Vec<PathToken>,
+        pruned: bool,
+        rules: &[Prune],
+        f: &mut impl FnMut(&[PathToken], &Number, bool),
+    ) {
+        let pruned = pruned || rules.iter().any(|rule| rule.matches(value, path));
+        match value {
+            Value::Array(a) => {
+                for (i, v) in a.iter().enumerate() {
+                    path.push(PathToken::Index(i));
+                    visit(v, path, pruned, rules, f);
+                    path.pop();
+                }
+            }
+            Value::Object(map) => {
+                for (k, v) in map {
+                    path.push(PathToken::Key(k.clone()));
+                    visit(v, path, pruned, rules, f);
+                    path.pop();
+                }
+            }
+            Value::Number(n) => f(path, n, pruned),
+            _ => {}
+        }
+    }
+
+    visit(root, &mut vec![], false, rules, &mut f);
+}
+
+fn audit(root: &Value, rules: &[Prune]) -> Audit {
+    let mut result = Audit::default();
+    walk(root, rules, |path, n, pruned| {
+        let Some(i) = n.as_i64() else {
+            result.ignored.push((json_pointer(path), n.clone()));
+            return;
+        };
+        let numbers = if pruned {
+            &mut result.skipped
+        } else {
+            &mut result.counted
+        };
+        numbers.push((json_pointer(path), i));
+    });
+    result
+}
+
+// An exact decimal number, `mantissa` * 10^-`scale`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Decimal {
+    mantissa: BigInt,
+    scale: u32,
+}
+
+impl Decimal {
+    fn rescaled(&self, scale: u32) -> BigInt {
+        &self.mantissa * BigInt::from(10).pow(scale - self.scale)
+    }
+
+    // Drops trailing zeros after the decimal point
+    fn normalized(mut self) -> Decimal {
+        let ten = BigInt::from(10);
+        while self.scale > 0 && (&self.mantissa % &ten).is_zero() {
+            self.mantissa /= &ten;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    fn is_integer(&self) -> bool {
+        self.clone().normalized().scale == 0
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        let n = self.clone().normalized();
+        if n.scale == 0 {
+            n.mantissa.to_i64()
+        } else {
+            None
+        }
+    }
+}
+
+// Largest power of ten a `Decimal` is scaled by, either way. JSON allows any
+// exponent, but writing those numbers out exactly quickly gets out of hand.
+const MAX_EXPONENT: u32 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DecimalError {
+    Invalid(String),
+    // The number is too large or too precise to be held exactly
+    ExponentOutOfRange(String),
+}
+
+// Parses the JSON number grammar, `-?int(.frac)?([eE][+-]?exp)?`
+impl FromStr for Decimal {
+    type Err = DecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || DecimalError::Invalid(s.to_string());
+        let (number, exponent) = match s.split_once(['e', 'E']) {
+            Some((number, exponent)) => (number, exponent.strip_prefix('+').unwrap_or(exponent)),
+            None => (s, "0"),
+        };
+        let exponent = exponent.parse::<BigInt>().map_err(|_| invalid())?;
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        let fraction = fraction.trim_end_matches('0');
+        let mantissa = format!("{}{}", integer, fraction)
+            .parse::<BigInt>()
+            .map_err(|_| invalid())?;
+        if mantissa.is_zero() {
+            return Ok(Decimal::default());
+        }
+
+        let exponent = (exponent - fraction.len())
+            .to_i64()
+            .filter(|exponent| exponent.unsigned_abs() <= MAX_EXPONENT as u64)
+            .ok_or_else(|| DecimalError::ExponentOutOfRange(s.to_string()))?;
+        let decimal = if exponent >= 0 {
+            Decimal {
+                mantissa: mantissa * BigInt::from(10).pow(exponent as u32),
+                scale: 0,
+            }
+        } else {
+            Decimal {
+                mantissa,
+                scale: exponent.unsigned_abs() as u32,
+            }
+        };
+        Ok(decimal.normalized())
+    }
+}
+
+impl Add for &Decimal {
+    type Output = Decimal;
+
+    fn add(self, other: &Decimal) -> Decimal {
+        let scale = self.scale.max(other.scale);
+        Decimal {
+            mantissa: self.rescaled(scale) + other.rescaled(scale),
+            scale,
+        }
+        .normalized()
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.magnitude().to_string();
+        let sign = if self.mantissa.is_negative() { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, integer, fraction)
+    }
+}
+
+// The exact sum of every number that wasn't pruned, along with the numbers
+// `extract_numbers` would have dropped
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ExactSum {
+    total: Decimal,
+    non_integral: Vec<(String, Decimal)>,
+    out_of_range: Vec<(String, Decimal)>,
+    // Numbers that can't be held exactly, which are left out of the total
+    excluded: Vec<(String, Number)>,
+}
+
+fn exact_sum(root: &Value, rules: &[Prune]) -> ExactSum {
+    let mut result = ExactSum::default();
+    walk(root, rules, |path, n, pruned| {
+        if pruned {
+            return;
+        }
+        // With `arbitrary_precision` this is the number as written
+        let Ok(n) = n.to_string().parse::<Decimal>() else {
+            result.excluded.push((json_pointer(path), n.clone()));
+            return;
+        };
+        result.total = &result.total + &n;
+        if !n.is_integer() {
+            result.non_integral.push((json_pointer(path), n));
+        } else if n.to_i64().is_none() {
+            result.out_of_range.push((json_pointer(path), n));
+        }
+    });
+    result
+}
+
+// Sums of the numbers in a JSON value, computed while it is being parsed. They
+// are wider than the numbers themselves so that no document that could ever be
+// read can overflow them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Subtotal {
+    all: i128,
+    skip_reds: i128,
+    // Whether the value itself is the string "red"
+    is_red: bool,
+}
+
+impl Subtotal {
+    fn number(n: Option<i64>) -> Self {
+        let n = n.map_or(0, i128::from);
+        Subtotal {
+            all: n,
+            skip_reds: n,
+            is_red: false,
+        }
+    }
+}
+
+struct SumVisitor;
+
+impl<'de> DeserializeSeed<'de> for SumVisitor {
+    type Value = Subtotal;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Subtotal, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+// Numbers that don't fit in an i64 are ignored, like `extract_numbers` does.
+impl<'de> Visitor<'de> for SumVisitor {
+    type Value = Subtotal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Subtotal, E> {
+        Ok(Subtotal::default())
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<Subtotal, E> {
+        Ok(Subtotal::number(Some(n)))
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<Subtotal, E> {
+        Ok(Subtotal::number(n.try_into().ok()))
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Subtotal, E> {
+        Ok(Subtotal::number(None))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Subtotal, E> {
+        Ok(Subtotal {
+            is_red: s == "red",
+            ..Default::default()
+        })
+    }
+
+    fn visit_unit<E>(self) -> Result<Subtotal, E> {
+        Ok(Subtotal::default())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Subtotal, A::Error> {
+        let mut subtotal = Subtotal::default();
+        while let Some(element) = seq.next_element_seed(SumVisitor)? {
+            subtotal.all += element.all;
+            subtotal.skip_reds += element.skip_reds;
+        }
+        Ok(subtotal)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Subtotal, A::Error> {
+        let mut subtotal = Subtotal::default();
+        let mut has_red = false;
+        // With `arbitrary_precision`, floats and integers that don't fit in 64
+        // bits come as a map holding the number as written, and are ignored.
+        // A real object whose first key is that same private key can't be told
+        // apart: it is dropped too, and is an error if it has more keys.
+        match map.next_key::<String>()? {
+            Some(key) if Some(key.as_str()) == number_key() => {
+                map.next_value::<IgnoredAny>()?;
+                return Ok(Subtotal::number(None));
+            }
+            Some(_) => {
+                let value = map.next_value_seed(SumVisitor)?;
+                subtotal.all += value.all;
+                subtotal.skip_reds += value.skip_reds;
+                has_red |= value.is_red;
+            }
+            None => return Ok(subtotal),
+        }
+        while map.next_key::<IgnoredAny>()?.is_some() {
+            let value = map.next_value_seed(SumVisitor)?;
+            subtotal.all += value.all;
+            subtotal.skip_reds += value.skip_reds;
+            has_red |= value.is_red;
+        }
+        if has_red {
+            subtotal.skip_reds = 0;
+        }
+        Ok(subtotal)
+    }
+}
+
+// The key serde_json gives numbers passed as a map isn't public, so it is read
+// back from a float, which always comes that way if any number does.
+fn number_key() -> Option<&'static str> {
+    static KEY: OnceLock<Option<String>> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let mut deserializer = serde_json::Deserializer::from_str("0.5");
+        FirstKey.deserialize(&mut deserializer).ok()
+    })
+    .as_deref()
+}
+
+// Deserializes a map into its first key
+struct FirstKey;
+
+impl<'de> DeserializeSeed<'de> for FirstKey {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<String, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FirstKey {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<String, A::Error> {
+        let key = map.next_key::<String>()?.unwrap_or_default();
+        map.next_value::<IgnoredAny>()?;
+        Ok(key)
+    }
+}
+
+// Returns the sum of all the numbers and the sum skipping objects with a "red"
+// value, without keeping the document in memory.
+fn streaming_sums(reader: impl Read) -> serde_json::Result<(i128, i128)> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let subtotal = SumVisitor.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok((subtotal.all, subtotal.skip_reds))
+}
+
 fn main() {
     let input = include_str!("../input.txt");
+
+    // `audit` followed by pruning rules lists every number and what happened to
+    // it, `exact` sums them exactly and lists the ones that aren't plain i64s
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+    if let Some("audit" | "exact") = command.as_deref() {
+        let rules = args
+            .map(|rule| rule.parse())
+            .collect::<Result<Vec<Prune>, _>>()
+            .unwrap();
+        let data: Value = serde_json::from_str(input).unwrap();
+        if command.as_deref() == Some("audit") {
+            let audit = audit(&data, &rules);
+            for (pointer, n) in &audit.counted {
+                println!("counted {} {}", pointer, n);
+            }
+            for (pointer, n) in &audit.skipped {
+                println!("skipped {} {}", pointer, n);
+            }
+            for (pointer, n) in &audit.ignored {
+                println!("ignored {} {}", pointer, n);
+            }
+            println!("{}", audit.sum());
+        } else {
+            let sum = exact_sum(&data, &rules);
+            for (pointer, n) in &sum.non_integral {
+                println!("non-integral {} {}", pointer, n);
+            }
+            for (pointer, n) in &sum.out_of_range {
+                println!("out-of-range {} {}", pointer, n);
+            }
+            for (pointer, n) in &sum.excluded {
+                println!("excluded {} {}", pointer, n);
+            }
+            println!("{}", sum.total);
+        }
+        return;
+    }
+
+    // A document given as an argument is streamed instead
+    if let Some(path) = std::env::args().nth(1) {
+        let reader = BufReader::new(File::open(path).unwrap());
+        let (all, skip_reds) = streaming_sums(reader).unwrap();
+        println!("{}", all);
+        println!("{}", skip_reds);
+        return;
+    }
+
     let data: Value = serde_json::from_str(input).unwrap();
 
-    let all_values = iterate_values(&data);
-    let numbers = extract_numbers(all_values);
-    println!("{}", numbers.sum::<i64>());
+    // The structure doesn't matter for the first part
+    println!("{}", scan_numbers(input.as_bytes()).sum::<i64>());
 
     let values_skip_reds = iterate_values_skip_reds(&data);
     let numbers_skip_reds = extract_numbers(values_skip_reds);
     println!("{}", numbers_skip_reds.sum::<i64>());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sums(input: &str) -> (i128, i128) {
+        let data: Value = serde_json::from_str(input).unwrap();
+        (
+            extract_numbers(iterate_values(&data)).map(i128::from).sum(),
+            extract_numbers(iterate_values_skip_reds(&data))
+                .map(i128::from)
+                .sum(),
+        )
+    }
+
+    #[test]
+    fn test_streaming_sums() {
+        let examples = [
+            ("[1,2,3]", (6, 6)),
+            (r#"{"a":2,"b":4}"#, (6, 6)),
+            ("[[[3]]]", (3, 3)),
+            (r#"{"a":{"b":4},"c":-1}"#, (3, 3)),
+            (r#"{"a":[-1,1]}"#, (0, 0)),
+            (r#"[-1,{"a":1}]"#, (0, 0)),
+            ("[]", (0, 0)),
+            ("{}", (0, 0)),
+            (r#"[1,{"c":"red","b":2},3]"#, (6, 4)),
+            (r#"{"d":"red","e":[1,2,3,4],"f":5}"#, (15, 0)),
+            (r#"[1,"red",5]"#, (6, 6)),
+            (
+                r#"{"red":1,"a":[{"b":"red","c":7}, 1.5, 18446744073709551615]}"#,
+                (8, 1),
+            ),
+        ];
+        for (input, expected) in examples {
+            assert_eq!(sums(input), expected, "{}", input);
+            assert_eq!(
+                streaming_sums(input.as_bytes()).unwrap(),
+                expected,
+                "{}",
+                input
+            );
+        }
+
+        let input = include_str!("../input.txt");
+        assert_eq!(streaming_sums(input.as_bytes()).unwrap(), sums(input));
+
+        let input = r#"[1,{"a":1e99999999999999999999,"b":-0.5,"c":2}]"#;
+        assert_eq!(streaming_sums(input.as_bytes()).unwrap(), (3, 3));
+
+        assert!(streaming_sums("[1,2".as_bytes()).is_err());
+        assert!(streaming_sums("[1,2] 3".as_bytes()).is_err());
+    }
+
+    // Reads `item` `count` times in a row, without ever holding more than one
+    // copy of it.
+    struct Repeated {
+        item: &'static [u8],
+        count: usize,
+        position: usize,
+    }
+
+    impl Read for Repeated {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.count == 0 {
+                return Ok(0);
+            }
+            let rest = &self.item[self.position..];
+            let len = rest.len().min(buf.len());
+            buf[..len].copy_from_slice(&rest[..len]);
+            self.position += len;
+            if self.position == self.item.len() {
+                self.position = 0;
+                self.count -= 1;
+            }
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_streaming_sums_large_document() {
+        let count = 200_000;
+        let items = Repeated {
+            item: br#"{"a":[1,2],"b":{"c":"red","d":[3]}},"#,
+            count,
+            position: 0,
+        };
+        let document = "[".as_bytes().chain(items).chain("4]".as_bytes());
+        let count = count as i128;
+        assert_eq!(
+            streaming_sums(BufReader::new(document)).unwrap(),
+            (6 * count + 4, 3 * count + 4)
+        );
+    }
+
+    #[test]
+    fn test_streaming_sums_number_key_object() {
+        let key = number_key().unwrap();
+        let input = format!("[1,{{\"{}\":5}}]", key);
+        assert_eq!(streaming_sums(input.as_bytes()).unwrap(), (1, 1));
+        let input = format!("[1,{{\"{}\":5,\"a\":2}}]", key);
+        assert!(streaming_sums(input.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_streaming_sums_past_i64() {
+        let input = format!("[{0},{0},{{\"a\":{0},\"b\":\"red\"}}]", i64::MAX);
+        let max = i64::MAX as i128;
+        assert_eq!(
+            streaming_sums(input.as_bytes()).unwrap(),
+            (3 * max, 2 * max)
+        );
+        let input = format!("[{0},{0}]", i64::MIN);
+        let min = i64::MIN as i128;
+        assert_eq!(
+            streaming_sums(input.as_bytes()).unwrap(),
+            (2 * min, 2 * min)
+        );
+    }
+
+    #[test]
+    fn test_selector() {
+        let path = [
+            PathToken::Key("a".to_string()),
+            PathToken::Index(2),
+            PathToken::Key("b".to_string()),
+        ];
+        for (selector, expected) in [
+            ("$.a[2].b", true),
+            ("$.a[*].b", true),
+            ("$.a.*.b", true),
+            ("$['a'][2]['b']", true),
+            ("$..b", true),
+            ("$..[2].b", true),
+            ("$..a..b", true),
+            ("$..*", true),
+            ("$.a[1].b", false),
+            ("$.a[2]", false),
+            ("$..a", false),
+            ("$", false),
+        ] {
+            let selector = selector.parse::<Selector>().unwrap();
+            assert_eq!(selector.matches(&path), expected, "{:?}", selector);
+        }
+        assert!("$".parse::<Selector>().unwrap().matches(&[]));
+
+        for invalid in ["a.b", "$.", "$.a[", "$[x]", "$..", "$a"] {
+            assert_eq!(
+                invalid.parse::<Selector>(),
+                Err(InvalidRule(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_audit() {
+        let data: Value = serde_json::from_str(
+            r#"{"a/b":[1,{"c":"red","d":2}],"e":{"f":[3,[4]],"g~":5},"h":"red","i":6}"#,
+        )
+        .unwrap();
+        fn pointers(numbers: &[(String, i64)]) -> Vec<&str> {
+            numbers.iter().map(|(p, _)| p.as_str()).collect()
+        }
+
+        let all = audit(&data, &[]);
+        assert_eq!(
+            pointers(&all.counted),
+            ["/a~1b/0", "/a~1b/1/d", "/e/f/0", "/e/f/1/0", "/e/g~0", "/i"]
+        );
+        assert!(all.skipped.is_empty());
+        assert_eq!(all.sum(), 21);
+
+        let reds = audit(&data, &["object-value:\"red\"".parse().unwrap()]);
+        assert_eq!(reds.sum(), 0);
+        assert_eq!(reds.skipped.len(), 6);
+
+        let prune = |rules: &[&str]| {
+            let rules = rules.iter().map(|r| r.parse().unwrap()).collect::<Vec<_>>();
+            audit(&data["e"], &rules)
+        };
+        assert_eq!(prune(&["depth:1"]).sum(), 8);
+        assert_eq!(pointers(&prune(&["depth:1"]).skipped), ["/f/1/0"]);
+        assert_eq!(prune(&["key:g~"]).sum(), 0);
+        assert_eq!(prune(&["array-value:3"]).sum(), 5);
+        assert_eq!(prune(&["object-value:3"]).sum(), 12);
+        assert_eq!(prune(&["value:4"]).sum(), 8);
+        assert_eq!(prune(&["select:$..[1]"]).sum(), 8);
+        assert_eq!(prune(&["select:$.f", "depth:0"]).sum(), 5);
+        assert_eq!(prune(&["select:$"]).sum(), 0);
+
+        assert_eq!(
+            "size:3".parse::<Prune>(),
+            Err(InvalidRule("size:3".to_string()))
+        );
+        assert!("value:red".parse::<Prune>().is_err());
+    }
+
+    #[test]
+    fn test_audit_ignored() {
+        let data: Value =
+            serde_json::from_str(r#"[1,2.5,{"a":18446744073709551615,"b":"red"},-3e2]"#).unwrap();
+        let audit = audit(&data, &["object-value:\"red\"".parse().unwrap()]);
+        assert_eq!(audit.sum(), 1);
+        assert!(audit.skipped.is_empty());
+        assert_eq!(
+            audit
+                .ignored
+                .iter()
+                .map(|(p, n)| (p.as_str(), n.to_string()))
+                .collect::<Vec<_>>(),
+            [
+                ("/1", "2.5".to_string()),
+                ("/2/a", "18446744073709551615".to_string()),
+                ("/3", "-3e2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_audit_sum_past_i64() {
+        let data: Value = serde_json::from_str(&format!("[{},1]", i64::MAX)).unwrap();
+        assert_eq!(audit(&data, &[]).sum(), i64::MAX as i128 + 1);
+    }
+
+    #[test]
+    fn test_audit_matches_skip_reds() {
+        let data: Value = serde_json::from_str(include_str!("../input.txt")).unwrap();
+        let audit = audit(&data, &[Prune::HasValue(Container::Object, "red".into())]);
+        assert_eq!(
+            audit.sum(),
+            extract_numbers(iterate_values_skip_reds(&data))
+                .map(i128::from)
+                .sum::<i128>()
+        );
+        assert_eq!(
+            audit.counted.len() + audit.skipped.len(),
+            extract_numbers(iterate_values(&data)).count()
+        );
+    }
+
+    #[test]
+    fn test_decimal() {
+        for (input, expected) in [
+            ("0", "0"),
+            ("-0", "0"),
+            ("12", "12"),
+            ("-1.50", "-1.5"),
+            ("0.001", "0.001"),
+            ("-0.25", "-0.25"),
+            ("1e3", "1000"),
+            ("1.5E+2", "150"),
+            ("25e-3", "0.025"),
+            ("-7e-1", "-0.7"),
+            (
+                "123456789012345678901234567890",
+                "123456789012345678901234567890",
+            ),
+        ] {
+            assert_eq!(
+                input.parse::<Decimal>().unwrap().to_string(),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            "1.5.2".parse::<Decimal>(),
+            Err(DecimalError::Invalid("1.5.2".to_string()))
+        );
+        for input in [
+            "1e99999999999999999999",
+            "1e-5000000000",
+            "1e10001",
+            "1.5e-10000",
+        ] {
+            assert_eq!(
+                input.parse::<Decimal>(),
+                Err(DecimalError::ExponentOutOfRange(input.to_string()))
+            );
+        }
+        assert_eq!(
+            "0e99999999999999999999".parse::<Decimal>(),
+            Ok(Decimal::default())
+        );
+        assert_eq!("1.000e-10000".parse::<Decimal>().unwrap().scale, 10000);
+        assert_eq!(
+            "1e10000".parse::<Decimal>().unwrap().to_string().len(),
+            10001
+        );
+
+        let sum = ["0.1", "0.2", "-0.3"]
+            .iter()
+            .map(|n| n.parse::<Decimal>().unwrap())
+            .fold(Decimal::default(), |sum, n| &sum + &n);
+        assert_eq!(sum, Decimal::default());
+
+        let n = "9223372036854775808".parse::<Decimal>().unwrap();
+        assert!(n.is_integer());
+        assert_eq!(n.to_i64(), None);
+        assert_eq!(
+            "-9223372036854775808".parse::<Decimal>().unwrap().to_i64(),
+            Some(i64::MIN)
+        );
+        assert_eq!("2.55e1".parse::<Decimal>().unwrap().to_i64(), None);
+        assert_eq!("2.00e1".parse::<Decimal>().unwrap().to_i64(), Some(20));
+    }
+
+    #[test]
+    fn test_exact_sum() {
+        let data: Value = serde_json::from_str(
+            r#"[1, 0.1, {"a": 18446744073709551616, "b": -0.35e1}, 1e2, [9223372036854775807, 1]]"#,
+        )
+        .unwrap();
+        let sum = exact_sum(&data, &[]);
+        assert_eq!(sum.total.to_string(), "27670116110564327521.6");
+        assert_eq!(
+            sum.non_integral
+                .iter()
+                .map(|(p, n)| (p.as_str(), n.to_string()))
+                .collect::<Vec<_>>(),
+            [("/1", "0.1".to_string()), ("/2/b", "-3.5".to_string())]
+        );
+        assert_eq!(
+            sum.out_of_range
+                .iter()
+                .map(|(p, n)| (p.as_str(), n.to_string()))
+                .collect::<Vec<_>>(),
+            [("/2/a", "18446744073709551616".to_string())]
+        );
+        // 1e2 is an integer, written in a way `as_i64` doesn't understand
+        assert_eq!(extract_numbers(iterate_values(&data)).count(), 3);
+
+        let rules = ["key:a".parse().unwrap()];
+        assert_eq!(
+            exact_sum(&data, &rules).total.to_string(),
+            "9223372036854775909.1"
+        );
+
+        let data: Value = serde_json::from_str(include_str!("../input.txt")).unwrap();
+        let sum = exact_sum(&data, &[]);
+        assert_eq!(
+            sum.total.to_i64(),
+            Some(extract_numbers(iterate_values(&data)).sum())
+        );
+        assert!(sum.non_integral.is_empty() && sum.out_of_range.is_empty());
+
+        let data: Value =
+            serde_json::from_str("[1, 1e99999999999999999999, 2.5e-5000000000]").unwrap();
+        let sum = exact_sum(&data, &[]);
+        assert_eq!(sum.total.to_string(), "1");
+        assert_eq!(
+            sum.excluded
+                .iter()
+                .map(|(p, n)| (p.as_str(), n.to_string()))
+                .collect::<Vec<_>>(),
+            [
+                ("/1", "1e99999999999999999999".to_string()),
+                ("/2", "2.5e-5000000000".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_numbers() {
+        for (input, expected) in [
+            ("[1,2,3]", vec![1, 2, 3]),
+            (r#"{"a":[-1,1],"b":-20}"#, vec![-1, 1, -20]),
+            (r#"{"12":"34","a5":6}"#, vec![6]),
+            (r#"["a\"1","\\",2,"\\\"3\"",4]"#, vec![2, 4]),
+            (r#"["\u0031", 5]"#, vec![5]),
+            ("[1.5, 2e3, -]", vec![1, 5, 2, 3]),
+            ("[99999999999999999999, 7]", vec![7]),
+            (r#"["unterminated 1"#, vec![]),
+        ] {
+            assert_eq!(
+                scan_numbers(input.as_bytes()).collect::<Vec<_>>(),
+                expected,
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_numbers_matches_parsing() {
+        let examples = [
+            "[1,2,3]",
+            r#"{"a":2,"b":4}"#,
+            "[[[3]]]",
+            r#"{"a":{"b":4},"c":-1}"#,
+            r#"{"a":[-1,1]}"#,
+            r#"[-1,{"a":1}]"#,
+            "[]",
+            "{}",
+            r#"{"-1":["2\"3", "\\", 4], "x\\\"5": -6}"#,
+            include_str!("../input.txt"),
+        ];
+        for input in examples {
+            // Object values are visited in key order after parsing
+            let data: Value = serde_json::from_str(input).unwrap();
+            let mut parsed = extract_numbers(iterate_values(&data)).collect::<Vec<_>>();
+            let mut scanned = scan_numbers(input.as_bytes()).collect::<Vec<_>>();
+            parsed.sort();
+            scanned.sort();
+            assert!(scanned == parsed, "{:.40}", input);
+        }
+    }
+}
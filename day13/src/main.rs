@@ -1,4 +1,7 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+#[cfg(test)]
+use std::collections::HashSet;
+use std::str::FromStr;
 
 use nom::{
     branch::alt,
@@ -36,10 +39,8 @@ fn parse_relationship(input: &str) -> IResult<&str, Relationship> {
 
 type RelationshipMatrix = HashMap<(String, String), i32>;
 
-fn arrangement_happiness(
-    relationship_matrix: &RelationshipMatrix,
-    arrangement: &Vec<String>,
-) -> i32 {
+#[cfg(test)]
+fn arrangement_happiness(relationship_matrix: &RelationshipMatrix, arrangement: &[String]) -> i32 {
     let mut total_happiness = 0;
     for i in 0..arrangement.len() {
         let j = (i + 1) % arrangement.len();
@@ -55,13 +56,12 @@ fn arrangement_happiness(
     total_happiness
 }
 
+#[cfg(test)]
 fn explore_arrangements(
     relationship_matrix: &RelationshipMatrix,
     current_arrangement: Vec<String>,
     remaining_people: HashSet<String>,
-    nodes_visited: &mut usize,
 ) -> (Vec<String>, i32) {
-    *nodes_visited += 1;
     if remaining_people.is_empty() {
         let current_arrangement_happiness =
             arrangement_happiness(relationship_matrix, &current_arrangement);
@@ -74,12 +74,7 @@ fn explore_arrangements(
                 next_arrangement.push(p.clone());
                 let mut next_remaining = remaining_people.clone();
                 next_remaining.remove(p);
-                explore_arrangements(
-                    relationship_matrix,
-                    next_arrangement,
-                    next_remaining,
-                    nodes_visited,
-                )
+                explore_arrangements(relationship_matrix, next_arrangement, next_remaining)
             })
             .collect::<Vec<_>>();
 
@@ -99,19 +94,576 @@ fn explore_arrangements(
     }
 }
 
+#[cfg(test)]
 fn find_best_arrangement(
     relationship_matrix: &RelationshipMatrix,
     people: &[String],
 ) -> (Vec<String>, i32) {
-    let mut nodes_visited = 0;
-    let solution = explore_arrangements(
+    explore_arrangements(
         relationship_matrix,
         vec![],
         people.iter().cloned().collect(),
-        &mut nodes_visited,
-    );
-    dbg!(nodes_visited);
-    solution
+    )
+}
+
+type GuestId = usize;
+
+// Guests by id, with the happiness each one gains by sitting next to each
+// other guest, and the happiness both of them gain together
+#[derive(Debug, Clone)]
+struct Guests {
+    names: Vec<String>,
+    happiness: Vec<Vec<i32>>,
+    pair_happiness: Vec<Vec<i32>>,
+}
+
+impl Guests {
+    fn new(relationship_matrix: &RelationshipMatrix, people: &[String]) -> Self {
+        let happiness = people
+            .iter()
+            .map(|a| {
+                people
+                    .iter()
+                    .map(|b| {
+                        if a == b {
+                            0
+                        } else {
+                            relationship_matrix[&(a.clone(), b.clone())]
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let pair_happiness = (0..people.len())
+            .map(|a| {
+                (0..people.len())
+                    .map(|b| happiness[a][b] + happiness[b][a])
+                    .collect()
+            })
+            .collect();
+        Guests {
+            names: people.to_vec(),
+            happiness,
+            pair_happiness,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn names(&self, arrangement: &[GuestId]) -> Vec<String> {
+        arrangement.iter().map(|&g| self.names[g].clone()).collect()
+    }
+
+    fn round_table_happiness(&self, arrangement: &[GuestId]) -> i32 {
+        if arrangement.len() < 2 {
+            return 0;
+        }
+        arrangement
+            .iter()
+            .zip(arrangement.iter().cycle().skip(1))
+            .map(|(&a, &b)| self.pair_happiness[a][b])
+            .sum()
+    }
+
+    // Rotations of a round table are the same arrangement, so guest 0 always
+    // sits first. `best[mask][last]` is the happiest way to seat the guests in
+    // `mask` after guest 0, ending with `last`.
+    fn best_round_table(&self) -> (Vec<GuestId>, i32) {
+        let n = self.len();
+        if n < 2 {
+            return ((0..n).collect(), 0);
+        }
+        let others = n - 1;
+        let full = (1 << others) - 1;
+        let mut best = vec![vec![None; others]; 1 << others];
+        let mut previous = vec![vec![0; others]; 1 << others];
+        for last in 0..others {
+            best[1 << last][last] = Some(self.pair_happiness[0][last + 1]);
+        }
+        for mask in 1..=full {
+            for last in 0..others {
+                let Some(happiness) = best[mask][last] else {
+                    continue;
+                };
+                for next in (0..others).filter(|&next| mask & (1 << next) == 0) {
+                    let happiness = happiness + self.pair_happiness[last + 1][next + 1];
+                    let entry = &mut best[mask | 1 << next][next];
+                    if entry.is_none_or(|h| happiness > h) {
+                        *entry = Some(happiness);
+                        previous[mask | 1 << next][next] = last;
+                    }
+                }
+            }
+        }
+
+        let (mut last, happiness) = (0..others)
+            .map(|last| {
+                (
+                    last,
+                    best[full][last].unwrap() + self.pair_happiness[last + 1][0],
+                )
+            })
+            .max_by_key(|&(_, happiness)| happiness)
+            .unwrap();
+        let mut arrangement = vec![];
+        let mut mask = full;
+        while mask != 0 {
+            arrangement.push(last + 1);
+            let before = previous[mask][last];
+            mask &= !(1 << last);
+            last = before;
+        }
+        arrangement.push(0);
+        arrangement.reverse();
+        debug_assert_eq!(self.round_table_happiness(&arrangement), happiness);
+        (arrangement, happiness)
+    }
+
+    // Happiness of the guest in each seat of a table. Guests at a round table
+    // of two sit next to each other on both sides.
+    fn seat_happiness(&self, table: &[GuestId], round: bool) -> Vec<i32> {
+        let k = table.len();
+        (0..k)
+            .map(|i| {
+                let neighbours = match (round, k) {
+                    (true, 0..=1) => vec![],
+                    (true, _) => vec![(i + k - 1) % k, (i + 1) % k],
+                    (false, _) => [i.checked_sub(1), Some(i + 1).filter(|&j| j < k)]
+                        .into_iter()
+                        .flatten()
+                        .collect(),
+                };
+                neighbours
+                    .into_iter()
+                    .map(|j| self.happiness[table[i]][table[j]])
+                    .sum()
+            })
+            .collect()
+    }
+
+    // Like `best_round_table`, but for a bench, where both ends are free
+    fn best_bench(&self) -> Vec<GuestId> {
+        let n = self.len();
+        if n < 2 {
+            return (0..n).collect();
+        }
+        let full = (1 << n) - 1;
+        let mut best = vec![vec![None; n]; 1 << n];
+        let mut previous = vec![vec![0; n]; 1 << n];
+        for first in 0..n {
+            best[1 << first][first] = Some(0);
+        }
+        for mask in 1..=full {
+            for last in 0..n {
+                let Some(happiness) = best[mask][last] else {
+                    continue;
+                };
+                for next in (0..n).filter(|&next| mask & (1 << next) == 0) {
+                    let happiness = happiness + self.pair_happiness[last][next];
+                    let entry = &mut best[mask | 1 << next][next];
+                    if entry.is_none_or(|h| happiness > h) {
+                        *entry = Some(happiness);
+                        previous[mask | 1 << next][next] = last;
+                    }
+                }
+            }
+        }
+
+        let mut last = (0..n).max_by_key(|&last| best[full][last]).unwrap();
+        let mut arrangement = vec![];
+        let mut mask = full;
+        while mask != 0 {
+            arrangement.push(last);
+            let before = previous[mask][last];
+            mask &= !(1 << last);
+            last = before;
+        }
+        arrangement.reverse();
+        arrangement
+    }
+
+    // Round tables of the given sizes maximizing the total happiness. The
+    // happiest round table for every set of guests is found first, with the
+    // lowest id guest sitting first, and then the sets are split among tables.
+    fn best_round_tables(&self, sizes: &[usize]) -> Vec<Vec<GuestId>> {
+        let n = self.len();
+        let mut best = vec![vec![None; n]; 1 << n];
+        let mut previous = vec![vec![0; n]; 1 << n];
+        for first in 0..n {
+            best[1 << first][first] = Some(0);
+        }
+        for mask in 1usize..1 << n {
+            let first = mask.trailing_zeros() as usize;
+            for last in 0..n {
+                let Some(happiness) = best[mask][last] else {
+                    continue;
+                };
+                for next in (first + 1..n).filter(|&next| mask & (1 << next) == 0) {
+                    let happiness = happiness + self.pair_happiness[last][next];
+                    let entry = &mut best[mask | 1 << next][next];
+                    if entry.is_none_or(|h| happiness > h) {
+                        *entry = Some(happiness);
+                        previous[mask | 1 << next][next] = last;
+                    }
+                }
+            }
+        }
+        let table_happiness = |mask: usize| {
+            let first = mask.trailing_zeros() as usize;
+            (0..n)
+                .filter_map(|last| {
+                    Some((last, best[mask][last]? + self.pair_happiness[last][first]))
+                })
+                .max_by_key(|&(_, happiness)| happiness)
+        };
+
+        // The happiest way to fill the tables so far with each set of guests,
+        // and the set of guests before the last table
+        let mut stages = vec![HashMap::from([(0usize, (0, 0usize))])];
+        for &size in sizes {
+            let mut stage = HashMap::new();
+            for (&mask, &(happiness, _)) in stages.last().unwrap() {
+                let free = !mask & ((1 << n) - 1);
+                let mut table = free;
+                while table != 0 {
+                    if table.count_ones() as usize == size {
+                        let happiness = happiness + table_happiness(table).unwrap().1;
+                        let entry = stage.entry(mask | table).or_insert((happiness, mask));
+                        if happiness > entry.0 {
+                            *entry = (happiness, mask);
+                        }
+                    }
+                    table = (table - 1) & free;
+                }
+                if size == 0 {
+                    stage.entry(mask).or_insert((happiness, mask));
+                }
+            }
+            stages.push(stage);
+        }
+
+        let mut mask = (1 << n) - 1;
+        let mut tables = vec![];
+        for stage in stages.iter().skip(1).rev() {
+            let before = stage[&mask].1;
+            let guests = mask & !before;
+            let mut table = vec![];
+            if guests != 0 {
+                let (mut last, _) = table_happiness(guests).unwrap();
+                let mut remaining = guests;
+                while remaining != 0 {
+                    table.push(last);
+                    let previous_guest = previous[remaining][last];
+                    remaining &= !(1 << last);
+                    last = previous_guest;
+                }
+                table.reverse();
+            }
+            tables.push(table);
+            mask = before;
+        }
+        tables.reverse();
+        tables
+    }
+
+    // The tables maximizing the happiness of the least happy guest, and then
+    // the total happiness. Guests are seated one by one, giving up as soon as
+    // someone is less happy than the least happy guest of the best seating so
+    // far. The first guest at a round table has the lowest id there.
+    fn fairest_tables(&self, sizes: &[usize], round: bool) -> Vec<Vec<GuestId>> {
+        struct Search<'a> {
+            guests: &'a Guests,
+            sizes: &'a [usize],
+            round: bool,
+            tables: Vec<Vec<GuestId>>,
+            seated: Vec<bool>,
+            // Least and total happiness of the best tables so far
+            best: Option<(i32, i32)>,
+            best_tables: Vec<Vec<GuestId>>,
+        }
+
+        impl Search<'_> {
+            fn least(&self) -> Option<i32> {
+                self.best.map(|(least, _)| least)
+            }
+
+            fn search(&mut self, least: i32) {
+                if self.least().is_some_and(|best| least < best) {
+                    return;
+                }
+                let t = self.tables.len() - 1;
+                if self.tables[t].len() == self.sizes[t] {
+                    let happiness = self.guests.seat_happiness(&self.tables[t], self.round);
+                    let least = happiness.into_iter().fold(least, i32::min);
+                    if t + 1 < self.sizes.len() {
+                        self.tables.push(vec![]);
+                        self.search(least);
+                        self.tables.pop();
+                        return;
+                    }
+                    let total = self
+                        .tables
+                        .iter()
+                        .flat_map(|table| self.guests.seat_happiness(table, self.round))
+                        .sum();
+                    if self.best.is_none_or(|best| (least, total) > best) {
+                        self.best = Some((least, total));
+                        self.best_tables = self.tables.clone();
+                    }
+                    return;
+                }
+
+                let lowest = match self.tables[t].first() {
+                    Some(&first) if self.round => first + 1,
+                    _ => 0,
+                };
+                for guest in lowest..self.guests.len() {
+                    if self.seated[guest] {
+                        continue;
+                    }
+                    self.seated[guest] = true;
+                    self.tables[t].push(guest);
+                    // The guest before the new one now has both neighbours,
+                    // unless they are first at a round table
+                    let table = &self.tables[t];
+                    let i = table.len().wrapping_sub(2);
+                    let mut next_least = least;
+                    if table.len() >= 2 && (i > 0 || !self.round) {
+                        let before = i
+                            .checked_sub(1)
+                            .map_or(0, |j| self.guests.happiness[table[i]][table[j]]);
+                        let happiness = before + self.guests.happiness[table[i]][guest];
+                        next_least = next_least.min(happiness);
+                    }
+                    self.search(next_least);
+                    self.tables[t].pop();
+                    self.seated[guest] = false;
+                }
+            }
+        }
+
+        let mut search = Search {
+            guests: self,
+            sizes,
+            round,
+            tables: vec![vec![]],
+            seated: vec![false; self.len()],
+            best: None,
+            best_tables: vec![],
+        };
+        search.search(i32::MAX);
+        search.best_tables
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SeatingError {
+    MissingRelationship(String, String),
+    UnknownGuest(String),
+    DuplicateGuest(String),
+    // A guest can't sit next to themselves
+    #[cfg(test)]
+    SameGuest(String),
+    TableSizes {
+        seats: usize,
+        guests: usize,
+    },
+}
+
+// The guests to seat and how they feel about each other. Every guest has a
+// relationship with every other guest.
+#[derive(Debug, Clone)]
+struct SeatingProblem {
+    people: Vec<String>,
+    relationship_matrix: RelationshipMatrix,
+}
+
+impl SeatingProblem {
+    fn new(relationships: &[Relationship]) -> Result<Self, SeatingError> {
+        let mut people = vec![];
+        let mut relationship_matrix = RelationshipMatrix::new();
+        for r in relationships {
+            for person in [&r.subject, &r.object] {
+                if !people.contains(person) {
+                    people.push(person.clone());
+                }
+            }
+            relationship_matrix.insert((r.subject.clone(), r.object.clone()), r.happiness);
+        }
+
+        for a in &people {
+            for b in people.iter().filter(|&b| b != a) {
+                if !relationship_matrix.contains_key(&(a.clone(), b.clone())) {
+                    return Err(SeatingError::MissingRelationship(a.clone(), b.clone()));
+                }
+            }
+        }
+
+        Ok(SeatingProblem {
+            people,
+            relationship_matrix,
+        })
+    }
+
+    fn check_guest(&self, name: &str) -> Result<(), SeatingError> {
+        if self.people.iter().any(|p| p == name) {
+            Ok(())
+        } else {
+            Err(SeatingError::UnknownGuest(name.to_owned()))
+        }
+    }
+
+    // Adds a guest who gains and makes others gain `happiness` with everyone
+    fn add_guest(&mut self, name: &str, happiness: i32) -> Result<(), SeatingError> {
+        if self.check_guest(name).is_ok() {
+            return Err(SeatingError::DuplicateGuest(name.to_owned()));
+        }
+        for person in &self.people {
+            self.relationship_matrix
+                .insert((person.clone(), name.to_owned()), happiness);
+            self.relationship_matrix
+                .insert((name.to_owned(), person.clone()), happiness);
+        }
+        self.people.push(name.to_owned());
+        Ok(())
+    }
+
+    fn remove_guest(&mut self, name: &str) -> Result<(), SeatingError> {
+        self.check_guest(name)?;
+        self.people.retain(|p| p != name);
+        self.relationship_matrix
+            .retain(|(subject, object), _| subject != name && object != name);
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn set_relationship(
+        &mut self,
+        subject: &str,
+        object: &str,
+        happiness: i32,
+    ) -> Result<(), SeatingError> {
+        self.check_guest(subject)?;
+        self.check_guest(object)?;
+        if subject == object {
+            return Err(SeatingError::SameGuest(subject.to_owned()));
+        }
+        self.relationship_matrix
+            .insert((subject.to_owned(), object.to_owned()), happiness);
+        Ok(())
+    }
+
+    fn guests(&self) -> Guests {
+        Guests::new(&self.relationship_matrix, &self.people)
+    }
+
+    fn solve(&self) -> (Vec<String>, i32) {
+        let guests = self.guests();
+        let (arrangement, happiness) = guests.best_round_table();
+        (guests.names(&arrangement), happiness)
+    }
+
+    fn solve_with(&self, layout: &Layout, objective: Objective) -> Result<Seating, SeatingError> {
+        let guests = self.guests();
+        let n = guests.len();
+        let (sizes, round) = match layout {
+            Layout::RoundTable => (vec![n], true),
+            Layout::Bench => (vec![n], false),
+            Layout::RoundTables(sizes) => (sizes.clone(), true),
+        };
+        if sizes.iter().sum::<usize>() != n {
+            return Err(SeatingError::TableSizes {
+                seats: sizes.iter().sum(),
+                guests: n,
+            });
+        }
+
+        let tables = match (objective, layout) {
+            (Objective::TotalHappiness, Layout::RoundTable) => vec![guests.best_round_table().0],
+            (Objective::TotalHappiness, Layout::Bench) => vec![guests.best_bench()],
+            (Objective::TotalHappiness, Layout::RoundTables(_)) => guests.best_round_tables(&sizes),
+            (Objective::Fairness, _) => guests.fairest_tables(&sizes, round),
+        };
+
+        let guest_happiness = tables
+            .iter()
+            .flat_map(|table| {
+                let happiness = guests.seat_happiness(table, round);
+                table
+                    .iter()
+                    .zip(happiness)
+                    .map(|(&g, h)| (guests.names[g].clone(), h))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Ok(Seating {
+            tables: tables.iter().map(|table| guests.names(table)).collect(),
+            guest_happiness,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Layout {
+    RoundTable,
+    Bench,
+    // Round tables of the given sizes
+    RoundTables(Vec<usize>),
+}
+
+// Written `round`, `bench` or `tables:<size>,<size>,...`
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round" => Ok(Layout::RoundTable),
+            "bench" => Ok(Layout::Bench),
+            _ => s
+                .strip_prefix("tables:")
+                .and_then(|sizes| sizes.split(',').map(|size| size.parse().ok()).collect())
+                .map(Layout::RoundTables)
+                .ok_or_else(|| s.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Objective {
+    TotalHappiness,
+    // Maximize the happiness of the least happy guest
+    Fairness,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "total" => Ok(Objective::TotalHappiness),
+            "fairness" => Ok(Objective::Fairness),
+            _ => Err(s.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Seating {
+    tables: Vec<Vec<String>>,
+    // Each guest's happiness, in seating order
+    guest_happiness: Vec<(String, i32)>,
+}
+
+impl Seating {
+    fn total(&self) -> i32 {
+        self.guest_happiness.iter().map(|(_, h)| h).sum()
+    }
+
+    fn least(&self) -> Option<i32> {
+        self.guest_happiness.iter().map(|&(_, h)| h).min()
+    }
 }
 
 fn main() {
@@ -123,53 +675,338 @@ fn main() {
         .map(|(_, r)| r)
         .collect();
 
-    let relationship_matrix: RelationshipMatrix = relationships
-        .iter()
-        .cloned()
-        .map(
-            |Relationship {
-                 subject,
-                 object,
-                 happiness,
-             }| { ((subject, object), happiness) },
-        )
-        .collect();
-
-    let remaining_people = relationships
-        .iter()
-        .map(
-            |Relationship {
-                 subject,
-                 object: _,
-                 happiness: _,
-             }| subject,
-        )
-        .cloned()
-        .collect::<HashSet<_>>()
-        .into_iter()
-        .collect::<Vec<_>>();
-
-    let (best_arrangement, best_arrangement_happiness) =
-        find_best_arrangement(&relationship_matrix, &remaining_people);
+    let mut problem = SeatingProblem::new(&relationships).unwrap();
+    let (best_arrangement, best_arrangement_happiness) = problem.solve();
 
     println!("{:?}", best_arrangement);
     println!("{}", best_arrangement_happiness);
 
-    let mut relationship_matrix_with_myself = relationship_matrix.clone();
-    let myself_string = String::from("Myself");
-    for people in remaining_people.iter() {
-        relationship_matrix_with_myself.insert((people.clone(), myself_string.clone()), 0);
-        relationship_matrix_with_myself.insert((myself_string.clone(), people.clone()), 0);
+    problem.add_guest("Myself", 0).unwrap();
+    let (best_arrangement_with_myself, best_arrangement_with_myself_happiness) = problem.solve();
+
+    println!("{:?}", best_arrangement_with_myself);
+    println!("{}", best_arrangement_with_myself_happiness);
+
+    // A layout and an objective, for everyone but myself, show how happy each
+    // guest is
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let [layout, objective] = args.as_slice() {
+        problem.remove_guest("Myself").unwrap();
+        let seating = problem
+            .solve_with(&layout.parse().unwrap(), objective.parse().unwrap())
+            .unwrap();
+        for table in &seating.tables {
+            println!("{:?}", table);
+        }
+        for (guest, happiness) in &seating.guest_happiness {
+            println!("{} {}", guest, happiness);
+        }
+        println!("{} {}", seating.total(), seating.least().unwrap_or(0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "Alice would gain 54 happiness units by sitting next to Bob.
+Alice would lose 79 happiness units by sitting next to Carol.
+Alice would lose 2 happiness units by sitting next to David.
+Bob would gain 83 happiness units by sitting next to Alice.
+Bob would lose 7 happiness units by sitting next to Carol.
+Bob would lose 63 happiness units by sitting next to David.
+Carol would lose 62 happiness units by sitting next to Alice.
+Carol would gain 60 happiness units by sitting next to Bob.
+Carol would gain 55 happiness units by sitting next to David.
+David would gain 46 happiness units by sitting next to Alice.
+David would lose 7 happiness units by sitting next to Bob.
+David would gain 41 happiness units by sitting next to Carol.";
+
+    fn relationship_matrix(input: &str) -> (RelationshipMatrix, Vec<String>) {
+        let mut people = vec![];
+        let mut relationship_matrix = RelationshipMatrix::new();
+        for line in input.lines() {
+            let (_, r) = parse_relationship(line).unwrap();
+            if !people.contains(&r.subject) {
+                people.push(r.subject.clone());
+            }
+            relationship_matrix.insert((r.subject, r.object), r.happiness);
+        }
+        (relationship_matrix, people)
     }
-    let mut remaining_people_with_myself = remaining_people.clone();
-    remaining_people_with_myself.push(myself_string);
 
-    let (best_arrangement_with_myself, best_arrangement_with_myself_happiness) =
-        find_best_arrangement(
-            &relationship_matrix_with_myself,
-            &remaining_people_with_myself,
+    // Guests named after their position, with `happiness(a, b)` for every
+    // ordered pair of them
+    fn numbered_relationship_matrix(
+        n: usize,
+        happiness: impl Fn(usize, usize) -> i32,
+    ) -> (RelationshipMatrix, Vec<String>) {
+        let people = (0..n).map(|i| format!("Guest{}", i)).collect::<Vec<_>>();
+        let mut relationship_matrix = RelationshipMatrix::new();
+        for (a, subject) in people.iter().enumerate() {
+            for (b, object) in people.iter().enumerate().filter(|&(b, _)| b != a) {
+                relationship_matrix.insert((subject.clone(), object.clone()), happiness(a, b));
+            }
+        }
+        (relationship_matrix, people)
+    }
+
+    // Happiness between -100 and 100, spread unevenly over the pairs
+    fn scattered_relationship_matrix(n: usize) -> (RelationshipMatrix, Vec<String>) {
+        numbered_relationship_matrix(n, |a, b| ((a * 37 + b * 59 + a * b) % 201) as i32 - 100)
+    }
+
+    #[test]
+    fn test_example() {
+        let (relationship_matrix, people) = relationship_matrix(EXAMPLE);
+        let guests = Guests::new(&relationship_matrix, &people);
+        let (arrangement, happiness) = guests.best_round_table();
+        assert_eq!(happiness, 330);
+        assert_eq!(guests.round_table_happiness(&arrangement), 330);
+        assert_eq!(arrangement[0], 0);
+    }
+
+    #[test]
+    fn test_best_round_table_large() {
+        let (relationship_matrix, people) = scattered_relationship_matrix(16);
+        let guests = Guests::new(&relationship_matrix, &people);
+        let (arrangement, happiness) = guests.best_round_table();
+        assert_eq!(arrangement.len(), 16);
+        assert_eq!(guests.round_table_happiness(&arrangement), happiness);
+    }
+
+    #[test]
+    fn test_seating_problem() {
+        let relationships = EXAMPLE
+            .lines()
+            .map(|line| parse_relationship(line).unwrap().1)
+            .collect::<Vec<_>>();
+        let mut problem = SeatingProblem::new(&relationships).unwrap();
+        assert_eq!(problem.solve().1, 330);
+
+        problem.add_guest("Myself", 0).unwrap();
+        let (_, expected) = find_best_arrangement(&problem.relationship_matrix, &problem.people);
+        assert_eq!(problem.solve().1, expected);
+        assert_eq!(
+            problem.add_guest("Alice", 0),
+            Err(SeatingError::DuplicateGuest("Alice".to_string()))
         );
 
-    println!("{:?}", best_arrangement_with_myself);
-    println!("{}", best_arrangement_with_myself_happiness);
+        problem.remove_guest("Myself").unwrap();
+        assert_eq!(problem.solve().1, 330);
+        assert_eq!(
+            problem.remove_guest("Myself"),
+            Err(SeatingError::UnknownGuest("Myself".to_string()))
+        );
+
+        problem.set_relationship("Carol", "Alice", 500).unwrap();
+        let (arrangement, happiness) = problem.solve();
+        let (_, expected) = find_best_arrangement(&problem.relationship_matrix, &problem.people);
+        assert_eq!(happiness, expected);
+        let alice = arrangement.iter().position(|p| p == "Alice").unwrap();
+        let carol = arrangement.iter().position(|p| p == "Carol").unwrap();
+        assert!(matches!((alice + 4 - carol) % 4, 1 | 3));
+        assert_eq!(
+            problem.set_relationship("Carol", "Eve", 1),
+            Err(SeatingError::UnknownGuest("Eve".to_string()))
+        );
+        assert_eq!(
+            problem.set_relationship("Carol", "Carol", 1),
+            Err(SeatingError::SameGuest("Carol".to_string()))
+        );
+
+        problem.remove_guest("Bob").unwrap();
+        problem.remove_guest("David").unwrap();
+        assert_eq!(
+            problem.solve(),
+            (
+                vec!["Alice".to_string(), "Carol".to_string()],
+                2 * (500 - 79)
+            )
+        );
+    }
+
+    #[test]
+    fn test_missing_relationship() {
+        let relationships = EXAMPLE
+            .lines()
+            .filter(|line| !line.starts_with("David would gain 41"))
+            .map(|line| parse_relationship(line).unwrap().1)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            SeatingProblem::new(&relationships).unwrap_err(),
+            SeatingError::MissingRelationship("David".to_string(), "Carol".to_string())
+        );
+    }
+
+    fn permutations(n: usize) -> Vec<Vec<GuestId>> {
+        if n == 0 {
+            return vec![vec![]];
+        }
+        permutations(n - 1)
+            .into_iter()
+            .flat_map(|p| {
+                (0..n).map(move |i| {
+                    let mut p = p.clone();
+                    p.insert(i, n - 1);
+                    p
+                })
+            })
+            .collect()
+    }
+
+    // Best (total, (least, total)) over every way to seat the guests
+    fn brute_force(guests: &Guests, sizes: &[usize], round: bool) -> (i32, (i32, i32)) {
+        let mut best_total = i32::MIN;
+        let mut fairest = (i32::MIN, i32::MIN);
+        for p in permutations(guests.len()) {
+            let mut rest = &p[..];
+            let mut happiness = vec![];
+            for &size in sizes {
+                let (table, r) = rest.split_at(size);
+                happiness.extend(guests.seat_happiness(table, round));
+                rest = r;
+            }
+            let total = happiness.iter().sum();
+            let least = happiness.iter().copied().min().unwrap_or(0);
+            best_total = best_total.max(total);
+            fairest = fairest.max((least, total));
+        }
+        (best_total, fairest)
+    }
+
+    #[test]
+    fn test_solve_with() {
+        let relationships = EXAMPLE
+            .lines()
+            .map(|line| parse_relationship(line).unwrap().1)
+            .collect::<Vec<_>>();
+        let problem = SeatingProblem::new(&relationships).unwrap();
+
+        let seating = problem
+            .solve_with(&Layout::RoundTable, Objective::TotalHappiness)
+            .unwrap();
+        assert_eq!(seating.total(), 330);
+        let mut happiness = seating.guest_happiness.clone();
+        happiness.sort();
+        assert_eq!(
+            happiness,
+            [
+                ("Alice".to_string(), 52),
+                ("Bob".to_string(), 76),
+                ("Carol".to_string(), 115),
+                ("David".to_string(), 87)
+            ]
+        );
+
+        // Cutting the table between Alice and David costs the least
+        let seating = problem
+            .solve_with(&Layout::Bench, Objective::TotalHappiness)
+            .unwrap();
+        assert_eq!(seating.total(), 330 - 44);
+
+        assert_eq!(
+            problem.solve_with(&Layout::RoundTables(vec![2, 3]), Objective::Fairness),
+            Err(SeatingError::TableSizes {
+                seats: 5,
+                guests: 4
+            })
+        );
+
+        assert_eq!("bench".parse(), Ok(Layout::Bench));
+        assert_eq!("tables:3,1".parse(), Ok(Layout::RoundTables(vec![3, 1])));
+        assert!("tables:3,x".parse::<Layout>().is_err());
+        assert_eq!("fairness".parse(), Ok(Objective::Fairness));
+    }
+
+    #[test]
+    fn test_large_layouts() {
+        let (relationship_matrix, people) = scattered_relationship_matrix(16);
+        let guests = Guests::new(&relationship_matrix, &people);
+        assert_eq!(guests.best_bench().len(), 16);
+
+        let (relationship_matrix, people) = scattered_relationship_matrix(12);
+        let guests = Guests::new(&relationship_matrix, &people);
+        let tables = guests.best_round_tables(&[4, 4, 4]);
+        assert_eq!(tables.iter().map(Vec::len).collect::<Vec<_>>(), [4, 4, 4]);
+    }
+
+    mod properties {
+        use proptest::prelude::*;
+
+        use super::super::*;
+        use super::{brute_force, numbered_relationship_matrix};
+
+        // Happiness between -100 and 100 for every ordered pair of up to 7 guests
+        fn relationships() -> impl Strategy<Value = (RelationshipMatrix, Vec<String>)> {
+            (1..=7usize).prop_flat_map(|n| {
+                prop::collection::vec(-100..=100i32, n * n)
+                    .prop_map(move |h| numbered_relationship_matrix(n, |a, b| h[a * n + b]))
+            })
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(64))]
+
+            #[test]
+            fn best_round_table_matches_brute_force(
+                (relationship_matrix, people) in relationships()
+            ) {
+                let guests = Guests::new(&relationship_matrix, &people);
+                let (arrangement, happiness) = guests.best_round_table();
+                let (_, expected) = find_best_arrangement(&relationship_matrix, &people);
+                prop_assert_eq!(happiness, expected);
+                prop_assert_eq!(
+                    arrangement_happiness(&relationship_matrix, &guests.names(&arrangement)),
+                    happiness
+                );
+                let mut seated = arrangement.clone();
+                seated.sort();
+                prop_assert_eq!(seated, (0..people.len()).collect::<Vec<_>>());
+            }
+
+            #[test]
+            fn layouts_and_objectives_match_brute_force(
+                (relationship_matrix, people) in relationships()
+            ) {
+                let n = people.len();
+                let relationships = relationship_matrix
+                    .iter()
+                    .map(|((subject, object), &happiness)| Relationship {
+                        subject: subject.clone(),
+                        object: object.clone(),
+                        happiness,
+                    })
+                    .collect::<Vec<_>>();
+                let mut problem = SeatingProblem::new(&relationships).unwrap();
+                problem.people = people;
+                let guests = problem.guests();
+
+                let mut layouts = vec![
+                    (Layout::RoundTable, vec![n], true),
+                    (Layout::Bench, vec![n], false),
+                ];
+                for first in 0..=n {
+                    let sizes = vec![first, (n - first) / 2, n - first - (n - first) / 2];
+                    layouts.push((Layout::RoundTables(sizes.clone()), sizes, true));
+                }
+                for (layout, sizes, round) in layouts {
+                    let (best_total, fairest) = brute_force(&guests, &sizes, round);
+
+                    let seating = problem
+                        .solve_with(&layout, Objective::TotalHappiness)
+                        .unwrap();
+                    prop_assert_eq!(seating.total(), best_total, "{:?}", layout);
+                    prop_assert_eq!(
+                        seating.tables.iter().map(Vec::len).collect::<Vec<_>>(),
+                        sizes
+                    );
+
+                    let seating = problem.solve_with(&layout, Objective::Fairness).unwrap();
+                    let least = seating.least().unwrap_or(0);
+                    prop_assert_eq!((least, seating.total()), fairest, "{:?}", layout);
+                }
+            }
+        }
+    }
 }